@@ -0,0 +1,298 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use html_escape::{
+    decode_html_entities, encode_double_quoted_attribute, encode_text,
+};
+use jiff::{civil, tz::TimeZone, Timestamp};
+use lazy_regex::regex;
+use ont::{Outline, Section};
+
+use crate::IoPipe;
+
+pub fn import(io: IoPipe) -> Result<()> {
+    let outline = from_html(&io.read_text()?);
+
+    // Input is HTML, there's no outline layout to preserve.
+    io.write_imported(&outline)
+}
+
+pub fn export(io: IoPipe) -> Result<()> {
+    let outline = io.read_outline()?;
+    io.write_text(to_html(&outline)?)
+}
+
+/// Render the bookmarks in an outline as a Netscape bookmark file.
+///
+/// Sections with an `uri` attribute are bookmarks and sections that contain
+/// bookmarks are folders. Other sections are left out.
+pub fn to_html(outline: &Outline) -> Result<String> {
+    let title = outline
+        .attrs
+        .get("title")
+        .map_or("Bookmarks", |a| a.as_str());
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "\
+<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">
+<TITLE>{0}</TITLE>
+<H1>{0}</H1>
+<DL><p>",
+        encode_text(title)
+    )?;
+    write_list(&mut out, &outline.children, 1)?;
+    writeln!(out, "</DL><p>")?;
+    Ok(out)
+}
+
+fn write_list(
+    out: &mut String,
+    sections: &[Section],
+    depth: usize,
+) -> Result<()> {
+    let indent = "    ".repeat(depth);
+    for s in sections {
+        if let Some(uri) = s.body.attrs.get("uri") {
+            write!(
+                out,
+                "{indent}<DT><A HREF=\"{}\"",
+                encode_double_quoted_attribute(uri)
+            )?;
+            if let Some(time) =
+                s.body.attrs.get("added").and_then(|a| unix_time(a))
+            {
+                write!(out, " ADD_DATE=\"{time}\"")?;
+            }
+            if let Some(tags) = s.body.get::<Vec<String>>("tags")? {
+                write!(
+                    out,
+                    " TAGS=\"{}\"",
+                    encode_double_quoted_attribute(&tags.join(","))
+                )?;
+            }
+            writeln!(out, ">{}</A>", encode_text(&s.head))?;
+
+            // Text lines become the description.
+            let text =
+                idm::to_string(&Outline::from_iter(s.body.children.clone()))?;
+            if !text.trim().is_empty() {
+                writeln!(out, "{indent}<DD>{}", encode_text(text.trim_end()))?;
+            }
+        } else if s.body.iter().any(|s| s.body.attrs.contains_key("uri")) {
+            writeln!(out, "{indent}<DT><H3>{}</H3>", encode_text(&s.head))?;
+            writeln!(out, "{indent}<DL><p>")?;
+            write_list(out, &s.body.children, depth + 1)?;
+            writeln!(out, "{indent}</DL><p>")?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse a Netscape bookmark file into an outline.
+///
+/// Folders become sections and bookmarks become sections with `uri`,
+/// `added` and `tags` attributes and descriptions as body text.
+pub fn from_html(text: &str) -> Outline {
+    // Open folders, innermost last. The first one is the root.
+    let mut stack = vec![Section::default()];
+    // Folder waiting for its list to start.
+    let mut folder = None;
+    // Start of the element contents that's being read.
+    let mut element: Option<(String, usize, Outline)> = None;
+    // Start of a description.
+    let mut description: Option<usize> = None;
+
+    for caps in regex!(
+        r#"(?s)<!--.*?-->|<(/?)([A-Za-z][A-Za-z0-9]*)((?:[^>"']|"[^"]*"|'[^']*')*)>"#
+    )
+    .captures_iter(text)
+    {
+        let span = caps.get(0).unwrap();
+        let Some(name) = caps.get(2) else {
+            // Comment.
+            continue;
+        };
+        let name = name.as_str().to_uppercase();
+        let is_close = &caps[1] == "/";
+
+        // Descriptions run until the next list element.
+        if matches!(name.as_str(), "DT" | "DL" | "H3" | "A") {
+            if let Some(start) = description.take() {
+                let desc = strip_tags(&text[start..span.start()]);
+                let top = &mut stack.last_mut().unwrap().body;
+                if let Some(bookmark) = top.children.last_mut() {
+                    for line in desc.lines() {
+                        if !line.trim().is_empty() {
+                            bookmark.body.push_line(line.trim());
+                        }
+                    }
+                }
+            }
+        }
+
+        match (name.as_str(), is_close) {
+            ("A", false) => {
+                let mut body = Outline::default();
+                for (key, value) in attributes(&caps[3]) {
+                    match key.as_str() {
+                        "HREF" => {
+                            body.attrs.insert("uri".into(), value);
+                        }
+                        "ADD_DATE" => {
+                            if let Some(added) = iso_time(&value) {
+                                body.attrs.insert("added".into(), added);
+                            }
+                        }
+                        "TAGS" => {
+                            let tags: Vec<String> = value
+                                .split(',')
+                                .map(|t| t.trim().replace(' ', "-"))
+                                .filter(|t| !t.is_empty())
+                                .collect();
+                            if !tags.is_empty() {
+                                body.attrs
+                                    .insert("tags".into(), tags.join(" "));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                element = Some((name, span.end(), body));
+            }
+            ("H3" | "H1", false) => {
+                element = Some((name, span.end(), Outline::default()));
+            }
+            (_, true) if element.as_ref().is_some_and(|e| e.0 == name) => {
+                let (_, start, body) = element.take().unwrap();
+                let title = strip_tags(&text[start..span.start()]);
+                let title = title.trim();
+                match name.as_str() {
+                    "A" => stack
+                        .last_mut()
+                        .unwrap()
+                        .body
+                        .push(Section::new(title, body)),
+                    "H3" => folder = Some(Section::new(title, body)),
+                    _ => {
+                        if title != "Bookmarks" && !title.is_empty() {
+                            stack[0]
+                                .body
+                                .attrs
+                                .insert("title".into(), title.into());
+                        }
+                    }
+                }
+            }
+            ("DD", false) => description = Some(span.end()),
+            ("DL", false) => {
+                if let Some(folder) = folder.take() {
+                    stack.push(folder);
+                }
+            }
+            ("DL", true) if stack.len() > 1 => {
+                let folder = stack.pop().unwrap();
+                stack.last_mut().unwrap().body.push(folder);
+            }
+            _ => {}
+        }
+    }
+
+    while stack.len() > 1 {
+        let folder = stack.pop().unwrap();
+        stack.last_mut().unwrap().body.push(folder);
+    }
+    stack.pop().unwrap().body
+}
+
+/// Attributes of an HTML tag with uppercase names and decoded values.
+fn attributes(text: &str) -> Vec<(String, String)> {
+    regex!(r#"([A-Za-z_:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)
+        .captures_iter(text)
+        .map(|caps| {
+            let value = caps.get(2).or(caps.get(3)).or(caps.get(4)).unwrap();
+            (
+                caps[1].to_uppercase(),
+                decode_html_entities(value.as_str()).into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Remove any markup from element text and decode entities.
+fn strip_tags(html: &str) -> String {
+    let text = regex!(r"<[^>]*>").replace_all(html, "");
+    decode_html_entities(&text).into_owned()
+}
+
+/// Convert a Unix timestamp to ISO 8601 time.
+fn iso_time(unix_time: &str) -> Option<String> {
+    let secs = unix_time.trim().parse::<i64>().ok()?;
+    Some(Timestamp::from_second(secs).ok()?.to_string())
+}
+
+/// Convert ISO 8601 time or date to a Unix timestamp.
+fn unix_time(iso_time: &str) -> Option<i64> {
+    let time = match iso_time.parse::<Timestamp>() {
+        Ok(time) => time,
+        // Times without a zone are taken to be UTC.
+        Err(_) => iso_time
+            .parse::<civil::DateTime>()
+            .ok()?
+            .to_zoned(TimeZone::UTC)
+            .ok()?
+            .timestamp(),
+    };
+    Some(time.as_second())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_roundtrip() {
+        let html = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+<DL><p>
+    <DT><H3 ADD_DATE="1700000000" PERSONAL_TOOLBAR_FOLDER="true">Toolbar</H3>
+    <DL><p>
+        <DT><A HREF="https://github.com/rsaarelm/ont" ADD_DATE="1704067200" ICON="data:image/png;base64,AAAA" TAGS="rust,idm notes">Ont &amp; IDM</A>
+        <DD>Outline tool
+second line
+    </DL><p>
+    <DT><A HREF="https://example.com/">Example</A>
+</DL><p>
+"#;
+
+        let outline = from_html(html);
+        assert_eq!(
+            idm::to_string(&outline).unwrap(),
+            "\
+Toolbar
+  Ont & IDM
+    :uri https://github.com/rsaarelm/ont
+    :added 2024-01-01T00:00:00Z
+    :tags rust idm-notes
+    Outline tool
+    second line
+Example
+  :uri https://example.com/
+"
+        );
+
+        let roundtrip = from_html(&to_html(&outline).unwrap());
+        assert_eq!(
+            idm::to_string(&roundtrip).unwrap(),
+            idm::to_string(&outline).unwrap()
+        );
+    }
+}
@@ -9,7 +9,7 @@ use anyhow::{bail, Result};
 use idm::ser::Indentation;
 use lazy_regex::regex;
 
-use crate::{Outline, SimpleOutline};
+use crate::{Document, Outline, SimpleOutline};
 
 pub fn read_directory(
     path: impl AsRef<Path>,
@@ -158,15 +158,38 @@ pub fn read_directory(
     Ok((idm::from_str(&buf)?, style.unwrap_or_default(), paths))
 }
 
+/// Write an outline into a collection directory.
+///
+/// Files that already exist in the directory are used as layout templates,
+/// sections that were not changed keep their exact original text.
 pub fn write_directory(
     path: impl AsRef<Path>,
     style: Indentation,
     data: &Outline,
+) -> Result<BTreeSet<PathBuf>> {
+    write(path.as_ref(), style, data, true)
+}
+
+/// Write an outline into a collection directory using the canonical IDM
+/// layout for every file.
+pub fn write_directory_normalized(
+    path: impl AsRef<Path>,
+    style: Indentation,
+    data: &Outline,
+) -> Result<BTreeSet<PathBuf>> {
+    write(path.as_ref(), style, data, false)
+}
+
+fn write(
+    path: &Path,
+    style: Indentation,
+    data: &Outline,
+    lossless: bool,
 ) -> Result<BTreeSet<PathBuf>> {
     // See that we can build all the contents successfully before deleting
     // anything.
     let mut files = BTreeMap::default();
-    build_files(&mut files, path.as_ref(), style, data)?;
+    build_files(&mut files, path, style, data, lossless)?;
 
     let paths = files.keys().cloned().collect();
 
@@ -185,6 +208,7 @@ fn build_files(
     path: impl AsRef<Path>,
     style: Indentation,
     data: &Outline,
+    lossless: bool,
 ) -> Result<()> {
     // Attribute block
     for (key, value) in &data.attrs {
@@ -216,17 +240,24 @@ fn build_files(
             continue;
         }
 
+        // Single-line files are read in as the rest of the headline after
+        // the file name.
+        let (head, line) = match section.head.split_once(' ') {
+            Some((head, line)) if section.body.is_empty() => (head, Some(line)),
+            _ => (section.head.as_str(), None),
+        };
+
         let mut is_directory = false;
 
-        let file_name = if let Some(name) = section.head.strip_suffix('/') {
+        let file_name = if let Some(name) = head.strip_suffix('/') {
             is_directory = true;
             name.to_owned()
-        } else if let Some(name) = section.head.strip_suffix(':') {
+        } else if let Some(name) = head.strip_suffix(':') {
             // File name ends in colon, it's some random non-IDM file.
             name.to_owned()
         } else {
             // Implicit filename, assume an .idm extension.
-            format!("{}.idm", section.head)
+            format!("{head}.idm")
         };
 
         if !is_valid_filename(&file_name) {
@@ -235,16 +266,108 @@ fn build_files(
 
         if is_directory {
             // Create a subdirectory.
-            build_files(files, path.as_ref().join(&section.head), style, &section.body)?;
+            build_files(
+                files,
+                path.as_ref().join(&section.head),
+                style,
+                &section.body,
+                lossless,
+            )?;
             continue;
         }
 
         let path = path.as_ref().join(file_name);
-        files.insert(path, idm::to_string_styled(style, &section.body)?);
+        // Toplevel sections with the same headline from concatenated
+        // collections would overwrite each other.
+        if files.contains_key(&path) {
+            bail!("build_files: multiple sections for file {path:?}");
+        }
+        if let Some(line) = line {
+            files.insert(path, line.to_owned());
+            continue;
+        }
+        let content = match fs::read_to_string(&path)
+            .ok()
+            .filter(|_| lossless)
+            .and_then(|text| Document::parse(text).ok())
+        {
+            // Use the existing file as the layout template.
+            Some(doc) => {
+                doc.render(&with_trailing_blanks(&section.body, doc.outline()))?
+            }
+            None => idm::to_string_styled(style, &section.body)?,
+        };
+        files.insert(path, content);
     }
     Ok(())
 }
 
+/// Blank lines at the end of a file end up after the file's section when
+/// the file is read into a collection, put them back so that unchanged files
+/// keep their exact text.
+fn with_trailing_blanks(body: &Outline, original: &Outline) -> Outline {
+    fn blanks(outline: &Outline) -> usize {
+        outline
+            .children
+            .iter()
+            .rev()
+            .take_while(|s| s.head.trim().is_empty() && s.body.is_empty())
+            .count()
+    }
+
+    let mut ret = body.clone();
+    let missing = blanks(original).saturating_sub(blanks(body));
+    ret.children.extend(
+        original.children[original.children.len() - missing..]
+            .iter()
+            .cloned(),
+    );
+    ret
+}
+
 fn is_valid_filename(s: impl AsRef<str>) -> bool {
     regex!(r"^:?[A-Za-z0-9_-][.A-Za-z0-9_-]*$").is_match(s.as_ref())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let files = [
+            (":title.idm", "Notes"),
+            ("f.idm", "-- comment\na\nb   \n\n"),
+            ("one.idm", "single line"),
+            ("sub/g.idm", "Q\n  z  \n\n\n"),
+            ("sub/h.idm", "R\n\n\n  x\nS"),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        for (path, text) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+
+        let (outline, style, _) = read_directory(dir.path()).unwrap();
+        write_directory(dir.path(), style, &outline).unwrap();
+
+        for (path, text) in files {
+            assert_eq!(
+                fs::read_to_string(dir.path().join(path)).unwrap(),
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_duplicate_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let outline: Outline =
+            idm::from_str("notes\n  from a\nnotes\n  from b").unwrap();
+        assert!(
+            write_directory(dir.path(), Default::default(), &outline).is_err()
+        );
+        assert!(fs::read_dir(dir.path()).unwrap().next().is_none());
+    }
+}
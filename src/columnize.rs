@@ -45,5 +45,5 @@ pub fn run(io: IoPipe) -> Result<()> {
         columns.children.push(elt);
     }
 
-    io.write(&columns)
+    io.write_normalized(&columns)
 }
@@ -0,0 +1,461 @@
+use std::ops::Range;
+
+use anyhow::{bail, Result};
+use idm::ser::Indentation;
+use indexmap::IndexMap;
+
+use crate::{parse, Outline, Section};
+
+/// Outline text that remembers its exact layout.
+///
+/// Parsing an outline loses details that don't show up in the data, like
+/// comments around attribute blocks, trailing whitespace, runs of blank lines
+/// and whether the text ends with a newline. A document keeps the original
+/// text and can write an edited version of its outline so that sections
+/// that were not changed keep their original bytes. Only changed sections are
+/// serialized anew.
+#[derive(Clone, Debug)]
+pub struct Document {
+    text: String,
+    style: Indentation,
+    outline: Outline,
+    root: Block,
+}
+
+/// Layout of an outline block in the document text.
+#[derive(Clone, Debug, Default)]
+struct Block {
+    /// Indentation of the block's headlines.
+    prefix: String,
+    /// Lines before the first section, the attribute block and any comments
+    /// and blank lines around it.
+    preamble: Range<usize>,
+    /// Span of the attribute lines within the preamble.
+    attr_lines: Option<Range<usize>>,
+    /// Attributes as they were parsed.
+    attrs: IndexMap<String, String>,
+    items: Vec<Item>,
+    /// Unparsed lines after the last section.
+    trailing: Range<usize>,
+}
+
+/// Layout of a section in the document text.
+#[derive(Clone, Debug)]
+struct Item {
+    /// Span of lines before the headline that don't show up in the outline
+    /// data.
+    trivia: Range<usize>,
+    /// Headline span.
+    head: Range<usize>,
+    body: Block,
+    /// Section as it was parsed.
+    section: Section,
+}
+
+impl Item {
+    /// Span of the whole item, including trivia.
+    fn span(&self) -> Range<usize> {
+        self.trivia.start..self.head.end.max(self.body.trailing.end)
+    }
+}
+
+impl Document {
+    pub fn parse(text: impl Into<String>) -> Result<Self> {
+        let text = text.into();
+        let outline: Outline = idm::from_str(&text)?;
+        let style = Indentation::infer(&text).unwrap_or_default();
+
+        let lines = Lines::new(&text);
+        let root = lines.block(0..lines.len(), &outline)?;
+
+        let ret = Document {
+            text,
+            style,
+            outline,
+            root,
+        };
+
+        // Make sure we really can reproduce the original.
+        if ret.render(&ret.outline)? != ret.text {
+            bail!("Document: Failed to capture document layout");
+        }
+
+        Ok(ret)
+    }
+
+    /// Outline parsed from the document.
+    pub fn outline(&self) -> &Outline {
+        &self.outline
+    }
+
+    /// Indentation style inferred from the document.
+    pub fn style(&self) -> Indentation {
+        self.style
+    }
+
+    /// Original document text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Serialize an edited version of the document's outline, keeping the
+    /// original text for everything that wasn't changed.
+    pub fn render(&self, outline: &Outline) -> Result<String> {
+        let mut out = String::new();
+        self.render_block(&mut out, &self.root, outline, "")?;
+        Ok(out)
+    }
+
+    fn render_block(
+        &self,
+        out: &mut String,
+        block: &Block,
+        outline: &Outline,
+        default_prefix: &str,
+    ) -> Result<()> {
+        let prefix = if block.items.is_empty() && block.attr_lines.is_none() {
+            default_prefix
+        } else {
+            &block.prefix
+        };
+
+        // Attribute block.
+        if outline.attrs.iter().eq(block.attrs.iter()) {
+            self.push(out, block.preamble.clone());
+        } else {
+            let attrs = Outline::new(outline.attrs.clone(), Vec::new());
+            let attrs = self.fresh(&attrs, prefix)?;
+            if let Some(span) = &block.attr_lines {
+                self.push(out, block.preamble.start..span.start);
+                push_str(out, &attrs);
+                self.push(out, span.end..block.preamble.end);
+            } else {
+                push_str(out, &attrs);
+                self.push(out, block.preamble.clone());
+            }
+        }
+
+        // Sections, matched to the original ones by headline.
+        let mut used = vec![false; block.items.len()];
+        let mut pos = 0;
+        for section in &outline.children {
+            let matches = |i: &usize| {
+                !used[*i] && block.items[*i].section.head == section.head
+            };
+            let Some(i) = (pos..block.items.len())
+                .find(matches)
+                .or_else(|| (0..pos).find(matches))
+            else {
+                // New section, write it out as is.
+                let section =
+                    Outline::from_iter(std::iter::once(section.clone()));
+                push_str(out, &self.fresh(&section, prefix)?);
+                continue;
+            };
+            used[i] = true;
+            pos = i + 1;
+
+            let item = &block.items[i];
+            if same_section(&item.section, section) {
+                self.push(out, item.span());
+            } else {
+                self.push(out, item.trivia.start..item.head.end);
+                self.render_block(
+                    out,
+                    &item.body,
+                    &section.body,
+                    &format!("{prefix}{}", indent_unit(self.style)),
+                )?;
+            }
+        }
+
+        self.push(out, block.trailing.clone());
+        Ok(())
+    }
+
+    /// Serialize a new outline fragment at the given indentation.
+    fn fresh(&self, outline: &Outline, prefix: &str) -> Result<String> {
+        if outline.is_empty() {
+            return Ok(String::new());
+        }
+
+        let text = idm::to_string_styled(self.style, outline)?;
+        let mut ret = String::new();
+        for line in text.split_inclusive('\n') {
+            if !line.trim().is_empty() {
+                ret.push_str(prefix);
+            }
+            ret.push_str(line);
+        }
+        Ok(ret)
+    }
+
+    fn push(&self, out: &mut String, span: Range<usize>) {
+        push_str(out, &self.text[span]);
+    }
+}
+
+/// Append text, making sure it starts on a new line.
+fn push_str(out: &mut String, s: &str) {
+    if s.is_empty() {
+        return;
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(s);
+}
+
+fn indent_unit(style: Indentation) -> String {
+    match style {
+        Indentation::Spaces(n) => " ".repeat(n),
+        Indentation::Tabs => "\t".into(),
+    }
+}
+
+/// Compare sections exactly, including attribute order.
+fn same_section(a: &Section, b: &Section) -> bool {
+    a.head == b.head && same_outline(&a.body, &b.body)
+}
+
+fn same_outline(a: &Outline, b: &Outline) -> bool {
+    a.attrs.iter().eq(b.attrs.iter())
+        && a.children.len() == b.children.len()
+        && a.children
+            .iter()
+            .zip(&b.children)
+            .all(|(a, b)| same_section(a, b))
+}
+
+/// Lines of the document text.
+struct Lines<'a> {
+    text: &'a str,
+    spans: Vec<Range<usize>>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(text: &'a str) -> Self {
+        let mut spans = Vec::new();
+        let mut pos = 0;
+        for line in text.split_inclusive('\n') {
+            spans.push(pos..pos + line.len());
+            pos += line.len();
+        }
+        Lines { text, spans }
+    }
+
+    fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    fn line(&self, i: usize) -> &'a str {
+        &self.text[self.spans[i].clone()]
+    }
+
+    /// Byte offset of the start of line `i`, or the end of text.
+    fn pos(&self, i: usize) -> usize {
+        self.spans.get(i).map_or(self.text.len(), |s| s.start)
+    }
+
+    fn span(&self, lines: Range<usize>) -> Range<usize> {
+        self.pos(lines.start)..self.pos(lines.end)
+    }
+
+    /// Indentation depth of line, `None` for blank lines.
+    fn depth(&self, i: usize) -> Option<usize> {
+        let line = self.line(i);
+        (!line.trim().is_empty()).then(|| parse::indentation(line).len())
+    }
+
+    fn is_comment(&self, i: usize) -> bool {
+        let line = self.line(i).trim();
+        line == "--" || line.starts_with("-- ")
+    }
+
+    fn is_attribute(&self, i: usize) -> bool {
+        let line = self.line(i).trim_start();
+        line.starts_with(':')
+            && line[1..].chars().next().is_some_and(|c| !c.is_whitespace())
+    }
+
+    /// Match the lines in range to the parsed outline.
+    fn block(&self, range: Range<usize>, outline: &Outline) -> Result<Block> {
+        let Some(depth) = range.clone().filter_map(|i| self.depth(i)).min()
+        else {
+            // Nothing but blank lines.
+            return Ok(Block {
+                trailing: self.span(range),
+                ..Default::default()
+            });
+        };
+
+        let prefix = range
+            .clone()
+            .find(|&i| self.depth(i) == Some(depth))
+            .map(|i| parse::indentation(self.line(i)).to_owned())
+            .unwrap_or_default();
+
+        let mut cursor = range.start;
+
+        // The attribute block swallows comments and blank lines around it.
+        let mut attr_lines: Option<Range<usize>> = None;
+        if !outline.attrs.is_empty() {
+            while cursor < range.end {
+                if self.depth(cursor).is_none() || self.is_comment(cursor) {
+                    cursor += 1;
+                } else if self.depth(cursor) == Some(depth)
+                    && self.is_attribute(cursor)
+                {
+                    let start = cursor;
+                    cursor += 1;
+                    // Multi-line values are indented under the key.
+                    while cursor < range.end
+                        && self.depth(cursor).is_some_and(|d| d > depth)
+                    {
+                        cursor += 1;
+                    }
+                    let span = self.span(start..cursor);
+                    attr_lines = Some(match attr_lines {
+                        Some(a) => a.start..span.end,
+                        None => span,
+                    });
+                } else {
+                    break;
+                }
+            }
+        }
+        let preamble = self.span(range.start..cursor);
+
+        let mut items = Vec::new();
+        for section in &outline.children {
+            let head = section.head.trim();
+            let is_trivia = head.is_empty() || section.head.starts_with("--");
+
+            // Find the headline, skipping over blank and comment lines the
+            // parser may have dropped.
+            let mut i = cursor;
+            let found = loop {
+                if i >= range.end {
+                    break None;
+                }
+                let line = self.line(i).trim();
+                let at_depth = self.depth(i).is_none_or(|d| d == depth);
+                if at_depth && line == head {
+                    break Some(i);
+                }
+                if self.depth(i).is_some() && !self.is_comment(i) {
+                    break None;
+                }
+                i += 1;
+            };
+
+            let Some(i) = found else {
+                if is_trivia {
+                    // Leave layout lines we can't place as trivia.
+                    continue;
+                }
+                bail!("Document: Could not find section {:?}", section.head);
+            };
+
+            // Body is the indented lines after the headline, trailing blank
+            // lines go to the parent block.
+            let mut end = i + 1;
+            let mut j = i + 1;
+            while j < range.end {
+                match self.depth(j) {
+                    None => {}
+                    Some(d) if d > depth => end = j + 1,
+                    _ => break,
+                }
+                j += 1;
+            }
+
+            let body = if self.depth(i).is_some() {
+                self.block(i + 1..end, &section.body)?
+            } else {
+                // Blank line, can't have a body.
+                Block::default()
+            };
+
+            items.push(Item {
+                trivia: self.span(cursor..i),
+                head: self.spans[i].clone(),
+                body,
+                section: section.clone(),
+            });
+            cursor = end;
+        }
+
+        Ok(Block {
+            prefix,
+            preamble,
+            attr_lines,
+            attrs: outline.attrs.clone(),
+            items,
+            trailing: self.span(cursor..range.end),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    const TEXT: &str = "\
+-- Comment before attributes
+:title Notes
+:tags a b
+
+
+Section one  \n  body
+
+  -- inner comment
+  more
+
+
+
+Last
+  x
+  y";
+
+    #[test]
+    fn test_roundtrip() {
+        let doc = Document::parse(TEXT).unwrap();
+        assert_eq!(doc.render(doc.outline()).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn test_edit() {
+        let doc = Document::parse(TEXT).unwrap();
+
+        // Changing one section leaves the others alone.
+        let mut outline = doc.outline().clone();
+        for s in outline.iter_mut() {
+            if s.head == "y" {
+                s.head = "z".into();
+            }
+        }
+        assert_eq!(
+            doc.render(&outline).unwrap(),
+            TEXT.replace("\n  y", "\n  z\n")
+        );
+
+        // Changing attributes rewrites only the attribute lines.
+        let mut outline = doc.outline().clone();
+        outline.set("title", &"Other").unwrap();
+        assert_eq!(
+            doc.render(&outline).unwrap(),
+            TEXT.replace(":title Notes", ":title Other")
+        );
+
+        // New sections are appended and removed ones disappear.
+        let mut outline = doc.outline().clone();
+        outline.children.retain(|s| s.head != "Last");
+        outline.push_line("New");
+        assert!(doc
+            .render(&outline)
+            .unwrap()
+            .ends_with("  more\n\n\n\nNew\n"));
+    }
+}
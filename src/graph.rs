@@ -0,0 +1,283 @@
+use std::{collections::HashMap, fmt::Write};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use html_escape::{encode_double_quoted_attribute as attr, encode_text};
+use indexmap::{IndexMap, IndexSet};
+use ont::{parse, Outline};
+
+use crate::{wiki, IoPipe};
+
+/// Output formats for the graph.
+#[derive(Copy, Clone, Debug, Default, ValueEnum)]
+pub enum Format {
+    /// Graphviz DOT.
+    #[default]
+    Dot,
+    /// GraphML XML.
+    Graphml,
+}
+
+/// Kinds of graph nodes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    /// Section with a WikiTitle headline.
+    Page,
+    /// Tag from a `:tags` attribute.
+    Tag,
+    /// Section with an `uri` attribute.
+    Bookmark,
+}
+
+impl NodeKind {
+    fn name(self) -> &'static str {
+        match self {
+            NodeKind::Page => "page",
+            NodeKind::Tag => "tag",
+            NodeKind::Bookmark => "bookmark",
+        }
+    }
+}
+
+/// Kinds of graph edges.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// WikiWord mentioning a page.
+    Reference,
+    /// Node tagged with a tag.
+    Tag,
+    /// Node nested in the body of another node.
+    Child,
+}
+
+impl EdgeKind {
+    fn name(self) -> &'static str {
+        match self {
+            EdgeKind::Reference => "reference",
+            EdgeKind::Tag => "tag",
+            EdgeKind::Child => "child",
+        }
+    }
+}
+
+/// Graph of the wiki pages, tags and bookmarks in an outline.
+#[derive(Clone, Debug, Default)]
+pub struct Graph {
+    /// Nodes by id with their kind and label.
+    pub nodes: IndexMap<String, (NodeKind, String)>,
+    /// Edges from node id to node id.
+    pub edges: IndexSet<(String, String, EdgeKind)>,
+}
+
+pub fn run(io: IoPipe, format: Format, bookmarks: bool) -> Result<()> {
+    let graph = Graph::new(&io.read_outline()?, bookmarks);
+    io.write_text(match format {
+        Format::Dot => graph.to_dot()?,
+        Format::Graphml => graph.to_graphml()?,
+    })
+}
+
+impl Graph {
+    /// Build a graph of the wiki pages, tags and optionally bookmarks of an
+    /// outline.
+    ///
+    /// Tags named after an existing wiki page link to the page instead of
+    /// getting a tag node of their own. WikiWords without a page are left
+    /// out.
+    pub fn new(outline: &Outline, bookmarks: bool) -> Self {
+        let pages: HashMap<String, &str> = outline
+            .iter()
+            .filter_map(|s| s.wiki_title())
+            .map(|t| (parse::camel_to_kebab(t), t))
+            .collect();
+
+        let mut ret = Graph::default();
+        // The context is the id of the innermost enclosing node and whether
+        // we're inside generated backlinks.
+        for ((parent, generated), s) in
+            outline.context_iter((None::<String>, false))
+        {
+            let in_page =
+                parent.as_ref().is_some_and(|p| p.starts_with("page:"));
+            if *generated || (in_page && wiki::is_generated_backlinks(s)) {
+                *generated = true;
+                continue;
+            }
+
+            let node = if let Some(title) = s.wiki_title() {
+                Some((NodeKind::Page, title, title))
+            } else if let (true, Some(uri)) =
+                (bookmarks, s.body.attrs.get("uri"))
+            {
+                Some((NodeKind::Bookmark, uri.as_str(), s.head.as_str()))
+            } else {
+                None
+            };
+
+            if let Some((kind, id, label)) = node {
+                let id = format!("{}:{id}", kind.name());
+                ret.nodes
+                    .entry(id.clone())
+                    .or_insert((kind, label.to_owned()));
+                if let Some(parent) = parent.replace(id.clone()) {
+                    ret.edges.insert((parent, id, EdgeKind::Child));
+                }
+            }
+
+            let Some(source) = parent.as_ref() else {
+                continue;
+            };
+
+            for word in wiki::mentioned_words(s) {
+                let target = format!("page:{word}");
+                let is_page =
+                    pages.get(&parse::camel_to_kebab(word)) == Some(&word);
+                if is_page && &target != source {
+                    ret.edges.insert((
+                        source.clone(),
+                        target,
+                        EdgeKind::Reference,
+                    ));
+                }
+            }
+
+            let tags = s.body.get::<Vec<String>>("tags").ok().flatten();
+            for tag in tags.unwrap_or_default() {
+                let target = if let Some(title) = pages.get(&tag) {
+                    format!("page:{title}")
+                } else {
+                    let id = format!("tag:{tag}");
+                    ret.nodes.entry(id.clone()).or_insert((NodeKind::Tag, tag));
+                    id
+                };
+                if &target != source {
+                    ret.edges.insert((source.clone(), target, EdgeKind::Tag));
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Render the graph in Graphviz DOT format.
+    ///
+    /// Pages are boxes, tags ellipses and bookmarks notes. References are
+    /// solid edges, tag edges dashed and nesting edges dotted.
+    pub fn to_dot(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(out, "digraph ont {{")?;
+        for (id, (kind, label)) in &self.nodes {
+            let shape = match kind {
+                NodeKind::Page => "box",
+                NodeKind::Tag => "ellipse",
+                NodeKind::Bookmark => "note",
+            };
+            writeln!(
+                out,
+                "  {} [label={}, shape={shape}];",
+                dot_id(id),
+                dot_id(label)
+            )?;
+        }
+        for (a, b, kind) in &self.edges {
+            let style = match kind {
+                EdgeKind::Reference => "solid",
+                EdgeKind::Tag => "dashed",
+                EdgeKind::Child => "dotted",
+            };
+            writeln!(out, "  {} -> {} [style={style}];", dot_id(a), dot_id(b))?;
+        }
+        writeln!(out, "}}")?;
+        Ok(out)
+    }
+
+    /// Render the graph in GraphML format with `label` and `kind` data.
+    pub fn to_graphml(&self) -> Result<String> {
+        let mut out = String::new();
+        writeln!(
+            out,
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="label" for="node" attr.name="label" attr.type="string"/>
+  <key id="kind" for="node" attr.name="kind" attr.type="string"/>
+  <key id="edge-kind" for="edge" attr.name="kind" attr.type="string"/>
+  <graph id="ont" edgedefault="directed">"#
+        )?;
+        for (id, (kind, label)) in &self.nodes {
+            writeln!(
+                out,
+                r#"    <node id="{}"><data key="label">{}</data><data key="kind">{}</data></node>"#,
+                attr(id),
+                encode_text(label),
+                kind.name()
+            )?;
+        }
+        for (a, b, kind) in &self.edges {
+            writeln!(
+                out,
+                r#"    <edge source="{}" target="{}"><data key="edge-kind">{}</data></edge>"#,
+                attr(a),
+                attr(b),
+                kind.name()
+            )?;
+        }
+        writeln!(out, "  </graph>\n</graphml>")?;
+        Ok(out)
+    }
+}
+
+/// Quote a string as a DOT identifier.
+fn dot_id(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_graph() {
+        let outline: Outline = idm::from_str(
+            "\
+OntTool
+  :tags rust idm-format
+  Uses IdmFormat, see NoSuchPage.
+  Repository
+    :uri https://github.com/rsaarelm/ont
+    :tags git
+IdmFormat
+  Backlinks
+    :generated backlinks
+    - OntTool",
+        )
+        .unwrap();
+
+        let graph = Graph::new(&outline, true);
+        assert_eq!(
+            graph.nodes.keys().collect::<Vec<_>>(),
+            vec![
+                "page:OntTool",
+                "tag:rust",
+                "bookmark:https://github.com/rsaarelm/ont",
+                "tag:git",
+                "page:IdmFormat"
+            ]
+        );
+
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|(a, b, k)| format!("{a} {} {b}", k.name()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                "page:OntTool tag tag:rust",
+                "page:OntTool tag page:IdmFormat",
+                "page:OntTool reference page:IdmFormat",
+                "page:OntTool child bookmark:https://github.com/rsaarelm/ont",
+                "bookmark:https://github.com/rsaarelm/ont tag tag:git",
+            ]
+        );
+    }
+}
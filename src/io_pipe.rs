@@ -1,105 +1,132 @@
-use std::{collections::BTreeSet, io::Read, path::PathBuf};
+use std::{
+    collections::BTreeSet,
+    io::{IsTerminal, Read},
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{bail, Result};
 use idm::ser::Indentation;
-use ont::{parse, Outline};
+use ont::{parse, Document, Outline, Section};
 
-use crate::IoArgs;
+use crate::{IoArgs, Merge};
+
+/// Refuse to print outlines read from a collection that are longer than this
+/// to a terminal unless stdout output was explicitly requested.
+const MAX_IMPLICIT_TERMINAL_LINES: usize = 1000;
 
 /// Structure that abstracts the input and output of subcommands.
 ///
 /// Either can be either a single file or a collection of files in a
-/// directory.
+/// directory. The source can also be a combination of several inputs.
 pub struct IoPipe {
     source: Source,
     dest: PathBuf,
 
+    /// Whether the user named the output explicitly instead of falling back
+    /// to stdout.
+    explicit_dest: bool,
+
     /// Indentation prefix to remove/add when piping fragments from the middle
     /// of a file.
     stdin_prefix: String,
+
+    /// Byte range of the input text to process when editing a fragment of a
+    /// file with `--at` or `--lines`. The rest of the text is written back
+    /// unchanged around the processed fragment.
+    fragment: Option<Range<usize>>,
 }
 
 impl IoPipe {
     pub fn read_text(&self) -> Result<String> {
+        if let Some((content, range)) = self.fragment() {
+            return Ok(content[range].to_owned());
+        }
+
         match self.source {
             Source::Stdin(ref content) => Ok(content.clone()),
             Source::File { ref content, .. } => Ok(content.clone()),
             Source::Collection {
                 ref outline, style, ..
-            } => Ok(idm::to_string_styled(style, outline)?),
+            }
+            | Source::Merged { ref outline, style } => {
+                Ok(idm::to_string_styled(style, outline)?)
+            }
         }
     }
 
     pub fn read_outline(&self) -> Result<Outline> {
-        match self.source {
-            Source::Collection { ref outline, .. } => Ok(outline.clone()),
-            Source::File { ref content, .. } => Ok(idm::from_str(content)?),
-            Source::Stdin(ref content) => {
-                if self.stdin_prefix.is_empty() {
-                    Ok(idm::from_str(content)?)
-                } else {
-                    let mut stripped = String::new();
-                    for line in content.lines() {
-                        if line.trim().is_empty() {
-                            stripped.push('\n');
-                            continue;
-                        }
-
-                        let Some(line) = line.strip_prefix(&self.stdin_prefix)
-                        else {
-                            // We can hit this if the input is mixing tabs and
-                            // spaces.
-                            bail!("read_outline: Shared indent failure")
-                        };
-                        stripped.push_str(line);
-                        stripped.push('\n');
-                    }
-                    Ok(idm::from_str(&stripped)?)
-                }
-            }
+        if let Some((content, range)) = self.fragment() {
+            return parse_indented(&content[range], &self.stdin_prefix);
         }
+
+        self.source.outline(&self.stdin_prefix)
     }
 
+    /// Write text output.
+    ///
+    /// The text is written as is, trailing whitespace like Markdown's line
+    /// break spaces is kept.
     pub fn write_text(&self, output: impl AsRef<str>) -> Result<()> {
-        let mut out: Box<dyn std::io::Write> =
-            if self.dest.to_str() == Some("-") {
-                // Return stdout as file
-                Box::new(std::io::stdout())
-            } else if self.dest.is_dir() {
-                bail!("Cannot write text to a directory");
-            } else {
-                // Open dest as file and return that
-                Box::new(std::fs::File::create(&self.dest)?)
-            };
+        let mut text = indent(output.as_ref(), &self.stdin_prefix);
+        if !text.is_empty() && !text.ends_with('\n') {
+            text.push('\n');
+        }
 
-        for line in output.as_ref().lines() {
-            if line.trim().is_empty() {
-                writeln!(out)?
-            } else {
-                writeln!(out, "{}{}", self.stdin_prefix, line.trim_end())?;
-            }
+        if self.is_dest_collection() {
+            bail!("Cannot write text to a directory");
         }
-        Ok(())
+        self.emit(&text)
     }
 
+    /// Write the output outline.
+    ///
+    /// Parts of the output that are unchanged from the input keep the exact
+    /// layout they had in the input text. Use this for commands that edit
+    /// their input, new outlines built from parts of the input would pick up
+    /// its comments.
     pub fn write(&self, output: &Outline) -> Result<()> {
+        self.write_outline(output, Layout::Lossless)
+    }
+
+    /// Write the output outline in the canonical IDM layout.
+    pub fn write_normalized(&self, output: &Outline) -> Result<()> {
+        self.write_outline(output, Layout::Normalized)
+    }
+
+    /// Write an outline converted from a non-IDM input.
+    ///
+    /// The indentation of the input says nothing about the IDM style, so the
+    /// output uses the default one.
+    pub fn write_imported(&self, output: &Outline) -> Result<()> {
+        self.write_outline(output, Layout::Imported)
+    }
+
+    fn write_outline(&self, output: &Outline, layout: Layout) -> Result<()> {
+        let lossless = layout == Layout::Lossless;
+        let style = if layout == Layout::Imported {
+            Indentation::default()
+        } else {
+            self.style()
+        };
+
         if self.dest.to_str() == Some("-") {
-            let s = idm::to_string_styled(self.style(), output)?;
-            if self.stdin_prefix.is_empty() {
-                print!("{s}");
-            } else {
-                // Reintroduce the stdin prefix when printing back to stdout.
-                for line in s.lines() {
-                    if line.trim().is_empty() {
-                        println!();
-                    } else {
-                        println!("{}{line}", self.stdin_prefix);
-                    }
-                }
+            let s = self.serialize(output, lossless, style)?;
+
+            // Reintroduce the stdin prefix when printing back to stdout.
+            self.emit(&indent(&s, &self.stdin_prefix))?;
+        } else if self.fragment.is_some() {
+            if self.is_dest_collection() {
+                bail!("Cannot write a file fragment to a directory");
             }
-        } else if self.dest.is_dir() {
-            let files_written =
-                ont::write_directory(&self.dest, self.style(), output)?;
+            let s = self.serialize(output, lossless, style)?;
+            self.emit(&indent(&s, &self.stdin_prefix))?;
+        } else if self.is_dest_collection() {
+            let files_written = if lossless {
+                ont::write_directory(&self.dest, style, output)?
+            } else {
+                ont::write_directory_normalized(&self.dest, style, output)?
+            };
             if let (Some(root), true) = (self.path(), self.is_in_place()) {
                 // Remove files that were initially read but were not written
                 // in output when rewriting a collection in place.
@@ -114,27 +141,138 @@ impl IoPipe {
         } else {
             std::fs::write(
                 &self.dest,
-                idm::to_string_styled(self.style(), output)?,
+                self.serialize(output, lossless, style)?,
             )?;
         }
         Ok(())
     }
 
+    /// Serialize an output outline, using the input text as the layout
+    /// template if `lossless` is set.
+    fn serialize(
+        &self,
+        output: &Outline,
+        lossless: bool,
+        style: Indentation,
+    ) -> Result<String> {
+        let original = match (&self.source, self.fragment()) {
+            _ if !lossless => None,
+            (_, Some((content, range))) => {
+                Some(strip_indent(&content[range], &self.stdin_prefix)?)
+            }
+            (Source::Stdin(content), None) => {
+                Some(strip_indent(content, &self.stdin_prefix)?)
+            }
+            (Source::File { content, .. }, None) => Some(content.clone()),
+            _ => None,
+        };
+
+        // Fall back to plain serialization if the document layout can't be
+        // captured.
+        if let Some(doc) = original.and_then(|text| Document::parse(text).ok())
+        {
+            return doc.render(output);
+        }
+
+        Ok(idm::to_string_styled(style, output)?)
+    }
+
+    /// Write final output text to stdout or the destination file.
+    ///
+    /// If a fragment of the input is being processed, the text replaces the
+    /// fragment and the rest of the input is written around it.
+    fn emit(&self, text: &str) -> Result<()> {
+        let text = if let Some((content, range)) = self.fragment() {
+            format!(
+                "{}{text}{}",
+                &content[..range.start],
+                &content[range.end..]
+            )
+        } else {
+            text.to_owned()
+        };
+
+        if self.dest.to_str() == Some("-") {
+            if is_terminal_flood(
+                self.explicit_dest,
+                matches!(
+                    self.source,
+                    Source::Collection { .. } | Source::Merged { .. }
+                ),
+                std::io::stdout().is_terminal(),
+                text.lines().count(),
+            ) {
+                bail!(
+                    "Refusing to dump a large collection to the terminal, \
+                    use `-o -` to write to stdout anyway"
+                );
+            }
+            print!("{text}");
+        } else {
+            std::fs::write(&self.dest, text)?;
+        }
+        Ok(())
+    }
+
+    /// Return the full input text and the range of the fragment being
+    /// processed, if any.
+    fn fragment(&self) -> Option<(&str, Range<usize>)> {
+        let range = self.fragment.clone()?;
+        match &self.source {
+            Source::Stdin(content) | Source::File { content, .. } => {
+                Some((content, range))
+            }
+            _ => None,
+        }
+    }
+
+    /// Write text files under the output collection directory.
+    ///
+    /// Use this for tools that produce something other than outlines from a
+    /// collection. Paths are relative to the output directory.
+    pub fn write_files(
+        &self,
+        files: impl IntoIterator<Item = (PathBuf, String)>,
+    ) -> Result<()> {
+        if !self.is_dest_collection() {
+            bail!("Output {:?} is not a directory", self.dest);
+        }
+
+        for (path, content) in files {
+            let path = self.dest.join(path);
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(path, content)?;
+        }
+        Ok(())
+    }
+
+    /// Whether the input is a whole collection.
+    ///
+    /// The toplevel sections of a collection outline are its files and
+    /// subdirectories.
+    pub fn is_source_collection(&self) -> bool {
+        matches!(self.source, Source::Collection { .. })
+            && self.fragment.is_none()
+    }
+
+    /// Output goes to a collection if it's an existing directory or if the
+    /// user asked for a new one by giving a path with a trailing slash.
+    pub fn is_dest_collection(&self) -> bool {
+        self.dest.is_dir() || self.dest.to_string_lossy().ends_with('/')
+    }
+
     fn is_in_place(&self) -> bool {
         match &self.source {
             Source::File { path, .. } => path == &self.dest,
             Source::Collection { path, .. } => path == &self.dest,
-            Source::Stdin(_) => false,
+            Source::Stdin(_) | Source::Merged { .. } => false,
         }
     }
 
     fn style(&self) -> Indentation {
-        match &self.source {
-            Source::Collection { style, .. } => *style,
-            Source::Stdin(content) | Source::File { content, .. } => {
-                Indentation::infer(content).unwrap_or_default()
-            }
-        }
+        self.source.style()
     }
 
     fn path(&self) -> Option<&PathBuf> {
@@ -142,7 +280,7 @@ impl IoPipe {
             Source::File { path, .. } | Source::Collection { path, .. } => {
                 Some(path)
             }
-            Source::Stdin(_) => None,
+            Source::Stdin(_) | Source::Merged { .. } => None,
         }
     }
 }
@@ -151,52 +289,126 @@ impl TryFrom<IoArgs> for IoPipe {
     type Error = anyhow::Error;
 
     fn try_from(value: IoArgs) -> Result<Self> {
-        if value.in_place && value.input.to_str() == Some("-") {
-            anyhow::bail!("Cannot use -i with standard input");
+        let Some(mut first) = value.input.first().cloned() else {
+            bail!("No input given");
+        };
+
+        if (value.at.is_some() || value.lines.is_some())
+            && value.input.len() > 1
+        {
+            bail!("Cannot process a fragment of multiple inputs");
         }
 
-        let mut stdin_prefix = String::new();
+        // Headline path to the fragment to process.
+        let mut at: Option<Vec<String>> = value.at.as_ref().map(|path| {
+            path.split('/')
+                .filter(|c| !c.is_empty())
+                .map(|c| c.to_owned())
+                .collect()
+        });
 
-        let source = if value.input.to_str() == Some("-") {
-            // Read stdin to string.
-            let mut input = String::new();
-            std::io::stdin().read_to_string(&mut input)?;
+        // Collection paths are resolved into a path inside a single file.
+        if let Some(ref mut at) = at {
+            if first.is_dir() {
+                let (file, rest) = resolve_collection_path(&first, at)?;
+                first = file;
+                *at = rest;
+            }
+        }
 
-            // See if all lines are indented by a common amount.
-            let nonempty_lines: Vec<&str> =
-                input.lines().filter(|l| !l.trim().is_empty()).collect();
-            let mut shared_indent = parse::indentation(
-                nonempty_lines.iter().copied().next().unwrap_or(""),
-            );
-            for &line in &nonempty_lines {
-                let prefix = parse::indentation(line);
-                if prefix.len() < shared_indent.len() {
-                    shared_indent = prefix;
+        if value.in_place && value.input.len() > 1 {
+            bail!("Cannot use -i with multiple inputs");
+        }
+
+        if value.in_place && first.to_str() == Some("-") {
+            bail!("Cannot use -i with standard input");
+        }
+
+        if value
+            .input
+            .iter()
+            .filter(|p| p.to_str() == Some("-"))
+            .count()
+            > 1
+        {
+            bail!("Cannot read standard input more than once");
+        }
+
+        let (source, mut stdin_prefix) = if value.input.len() == 1 {
+            Source::read(&first)?
+        } else {
+            let mut style = None;
+            let mut outline = Outline::default();
+
+            for path in &value.input {
+                let (source, stdin_prefix) = Source::read(path)?;
+                // Use the style of the first input for the whole thing.
+                style.get_or_insert(source.style());
+                let input = source.outline(&stdin_prefix)?;
+
+                match value.merge {
+                    Merge::Concat => {
+                        for (k, v) in input.attrs {
+                            outline.attrs.entry(k).or_insert(v);
+                        }
+                        outline.children.extend(input.children);
+                    }
+                    Merge::Wrap => {
+                        let mut head = path.to_string_lossy().into_owned();
+                        // Directory heads end in slash like they do in
+                        // collections.
+                        if path.is_dir() && !head.ends_with('/') {
+                            head.push('/');
+                        }
+                        outline.push(Section::new(head, input));
+                    }
+                    Merge::Head => outline.merge(input),
                 }
             }
-            stdin_prefix = shared_indent.to_owned();
 
-            Source::Stdin(input)
-        } else if value.input.is_dir() {
-            let (outline, style, files) = ont::read_directory(&value.input)?;
-            Source::Collection {
-                path: value.input.clone(),
-                files,
-                style,
-                outline,
-            }
-        } else if value.input.is_file() {
-            let content = std::fs::read_to_string(&value.input)?;
-            Source::File {
-                path: value.input.clone(),
-                content,
-            }
-        } else {
-            bail!("Input is not a file or a directory");
+            (
+                Source::Merged {
+                    style: style.unwrap_or_default(),
+                    outline,
+                },
+                String::new(),
+            )
         };
 
+        let mut fragment = None;
+        if at.is_some() || value.lines.is_some() {
+            let content = match &source {
+                Source::Stdin(content) | Source::File { content, .. } => {
+                    content
+                }
+                _ => bail!("Can only process fragments of single files"),
+            };
+
+            let range = match (&at, &value.lines) {
+                (Some(at), _) => fragment_at(content, at)?,
+                (None, Some(lines)) => {
+                    let Some((a, b)) =
+                        lines.split_once(':').and_then(|(a, b)| {
+                            Some((a.parse().ok()?, b.parse().ok()?))
+                        })
+                    else {
+                        bail!("Bad line range {lines:?}, expected N:M");
+                    };
+                    line_range(content, a, b)?
+                }
+                (None, None) => unreachable!(),
+            };
+
+            // Whole file paths from collections have no fragment.
+            if range != (0..content.len()) {
+                stdin_prefix =
+                    shared_indent(&content[range.clone()]).to_owned();
+                fragment = Some(range);
+            }
+        }
+
         let dest = match &value.output {
-            None if value.in_place => value.input,
+            None if value.in_place => first,
             Some(_) if value.in_place => {
                 bail!("Cannot use -i with output file");
             }
@@ -207,7 +419,9 @@ impl TryFrom<IoArgs> for IoPipe {
         Ok(IoPipe {
             source,
             dest,
+            explicit_dest: value.output.is_some() || value.in_place,
             stdin_prefix,
+            fragment,
         })
     }
 }
@@ -224,4 +438,299 @@ enum Source {
         style: Indentation,
         outline: Outline,
     },
+    /// Outline combined from several inputs.
+    Merged {
+        style: Indentation,
+        outline: Outline,
+    },
+}
+
+impl Source {
+    /// Read a source from a path and return it along with the shared
+    /// indentation prefix of stdin input.
+    fn read(path: &Path) -> Result<(Source, String)> {
+        let mut stdin_prefix = String::new();
+
+        let source = if path.to_str() == Some("-") {
+            // Read stdin to string.
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input)?;
+
+            // See if all lines are indented by a common amount.
+            stdin_prefix = shared_indent(&input).to_owned();
+
+            Source::Stdin(input)
+        } else if path.is_dir() {
+            let (outline, style, files) = ont::read_directory(path)?;
+            Source::Collection {
+                path: path.to_owned(),
+                files,
+                style,
+                outline,
+            }
+        } else if path.is_file() {
+            let content = std::fs::read_to_string(path)?;
+            Source::File {
+                path: path.to_owned(),
+                content,
+            }
+        } else {
+            bail!("Input {path:?} is not a file or a directory");
+        };
+
+        Ok((source, stdin_prefix))
+    }
+
+    fn outline(&self, stdin_prefix: &str) -> Result<Outline> {
+        match self {
+            Source::Collection { outline, .. }
+            | Source::Merged { outline, .. } => Ok(outline.clone()),
+            Source::File { content, .. } => Ok(idm::from_str(content)?),
+            Source::Stdin(content) => parse_indented(content, stdin_prefix),
+        }
+    }
+
+    fn style(&self) -> Indentation {
+        match self {
+            Source::Collection { style, .. } | Source::Merged { style, .. } => {
+                *style
+            }
+            Source::Stdin(content) | Source::File { content, .. } => {
+                Indentation::infer(content).unwrap_or_default()
+            }
+        }
+    }
+}
+
+/// How output outlines are laid out.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Layout {
+    /// Keep the layout of the input for unchanged parts.
+    Lossless,
+    /// Canonical IDM layout in the style of the input.
+    Normalized,
+    /// Canonical IDM layout in the default style.
+    Imported,
+}
+
+/// Find the smallest indentation shared by all nonempty lines of text.
+fn shared_indent(text: &str) -> &str {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let mut shared_indent = parse::indentation(lines.next().unwrap_or(""));
+    for line in lines {
+        let prefix = parse::indentation(line);
+        if prefix.len() < shared_indent.len() {
+            shared_indent = prefix;
+        }
+    }
+    shared_indent
+}
+
+/// Remove `prefix` from the start of every nonempty line of text.
+fn strip_indent(text: &str, prefix: &str) -> Result<String> {
+    let mut stripped = String::new();
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            stripped.push_str(line);
+            continue;
+        }
+
+        let Some(line) = line.strip_prefix(prefix) else {
+            // We can hit this if the input is mixing tabs and spaces.
+            bail!("read_outline: Shared indent failure")
+        };
+        stripped.push_str(line);
+    }
+    Ok(stripped)
+}
+
+/// Parse an outline from text where every nonempty line starts with
+/// `prefix`.
+fn parse_indented(text: &str, prefix: &str) -> Result<Outline> {
+    Ok(idm::from_str(&strip_indent(text, prefix)?)?)
+}
+
+/// Add `prefix` to the start of every nonempty line of text.
+fn indent(text: &str, prefix: &str) -> String {
+    let mut ret = String::new();
+    for line in text.split_inclusive('\n') {
+        if !line.trim().is_empty() {
+            ret.push_str(prefix);
+        }
+        ret.push_str(line);
+    }
+    ret
+}
+
+/// Find the file in a collection that a headline path points into.
+///
+/// Return the file path and the remaining headline path inside the file.
+fn resolve_collection_path(
+    root: &Path,
+    at: &[String],
+) -> Result<(PathBuf, Vec<String>)> {
+    let mut dir = root.to_owned();
+    for (i, name) in at.iter().enumerate() {
+        // Non-IDM files show up with a trailing colon in collection
+        // outlines.
+        let name = name.strip_suffix(':').unwrap_or(name);
+
+        let path = dir.join(name);
+        if path.is_dir() {
+            dir = path;
+            continue;
+        }
+
+        for path in [dir.join(format!("{name}.idm")), path] {
+            if path.is_file() {
+                return Ok((path, at[i + 1..].to_vec()));
+            }
+        }
+        break;
+    }
+
+    bail!(
+        "Path {:?} does not point to a file in {root:?}",
+        at.join("/")
+    );
+}
+
+/// Byte ranges of the lines in text, including the trailing newlines.
+fn line_spans(text: &str) -> Vec<Range<usize>> {
+    let mut ret = Vec::new();
+    let mut pos = 0;
+    for line in text.split_inclusive('\n') {
+        ret.push(pos..pos + line.len());
+        pos += line.len();
+    }
+    ret
+}
+
+/// Byte range of one-based inclusive line range `a:b` in text.
+fn line_range(text: &str, a: usize, b: usize) -> Result<Range<usize>> {
+    let spans = line_spans(text);
+    if a == 0 || a > b || b > spans.len() {
+        bail!("Line range {a}:{b} is not inside the input");
+    }
+    Ok(spans[a - 1].start..spans[b - 1].end)
+}
+
+/// Byte range of the body of the section at headline path `at` in text.
+fn fragment_at(text: &str, at: &[String]) -> Result<Range<usize>> {
+    let spans = line_spans(text);
+    let line = |i: usize| &text[spans[i].clone()];
+    let depth = |i: usize| {
+        let line = line(i);
+        (!line.trim().is_empty()).then(|| parse::indentation(line).len())
+    };
+
+    let (mut lo, mut hi) = (0, spans.len());
+    for name in at {
+        // Headlines of the current block are at its shallowest indentation.
+        let Some(top) = (lo..hi).filter_map(depth).min() else {
+            bail!("Section {name:?} not found");
+        };
+
+        let Some(i) = (lo..hi).find(|&i| {
+            let head = line(i).trim();
+            depth(i) == Some(top)
+                && (head == name || parse::important(head) == Some(name))
+        }) else {
+            bail!("Section {name:?} not found");
+        };
+
+        // Body continues until the next line at the headline's depth.
+        let mut end = i + 1;
+        while end < hi && depth(end).is_none_or(|d| d > top) {
+            end += 1;
+        }
+        (lo, hi) = (i + 1, end);
+    }
+
+    // Leave trailing blank lines outside the fragment.
+    while hi > lo && depth(hi - 1).is_none() {
+        hi -= 1;
+    }
+
+    if lo == hi {
+        bail!("Section {:?} has no body", at.join("/"));
+    }
+
+    Ok(spans[lo].start..spans[hi - 1].end)
+}
+
+/// Whether output that would flood the terminal should be refused.
+///
+/// Output read from a collection is only printed to a terminal when it's
+/// short or stdout was named explicitly as the destination.
+fn is_terminal_flood(
+    explicit_dest: bool,
+    from_collection: bool,
+    is_terminal: bool,
+    line_count: usize,
+) -> bool {
+    !explicit_dest
+        && from_collection
+        && is_terminal
+        && line_count > MAX_IMPLICIT_TERMINAL_LINES
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terminal_flood() {
+        let big = MAX_IMPLICIT_TERMINAL_LINES + 1;
+        assert!(is_terminal_flood(false, true, true, big));
+        assert!(!is_terminal_flood(true, true, true, big));
+        assert!(!is_terminal_flood(false, false, true, big));
+        assert!(!is_terminal_flood(false, true, false, big));
+        assert!(!is_terminal_flood(
+            false,
+            true,
+            true,
+            MAX_IMPLICIT_TERMINAL_LINES
+        ));
+    }
+
+    #[test]
+    fn test_indent() {
+        assert_eq!(indent("a  \n\n  b\n", "  "), "  a  \n\n    b\n");
+    }
+
+    #[test]
+    fn test_fragment_at() {
+        let text = "\
+Intro
+Notes
+  Table
+    a 1
+    b 2
+
+  Other
+    c
+Outro
+";
+        let at = |path: &str| {
+            let path: Vec<String> =
+                path.split('/').map(|s| s.to_owned()).collect();
+            fragment_at(text, &path).map(|r| &text[r])
+        };
+
+        assert_eq!(at("Notes/Table").unwrap(), "    a 1\n    b 2\n");
+        assert_eq!(at("Notes/Other").unwrap(), "    c\n");
+        assert_eq!(
+            at("Notes").unwrap(),
+            "  Table\n    a 1\n    b 2\n\n  Other\n    c\n"
+        );
+        assert!(at("Table").is_err());
+        assert!(at("Intro").is_err());
+
+        assert_eq!(
+            &text[line_range(text, 3, 4).unwrap()],
+            "  Table\n    a 1\n"
+        );
+        assert!(line_range(text, 3, 100).is_err());
+    }
 }
@@ -0,0 +1,290 @@
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use lazy_regex::regex;
+use ont::{Outline, Section};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::IoPipe;
+
+pub fn export(io: IoPipe, data: bool) -> Result<()> {
+    let outline = io.read_outline()?;
+
+    let json = if data {
+        serde_json::to_string_pretty(&data_value(&outline))?
+    } else {
+        serde_json::to_string_pretty(&JsonOutline::from(outline))?
+    };
+
+    io.write_text(json)
+}
+
+pub fn import(io: IoPipe, data: bool) -> Result<()> {
+    let text = io.read_text()?;
+
+    let outline: Outline = if data {
+        data_outline(&text)?
+    } else {
+        serde_json::from_str::<JsonOutline>(&text)?.into()
+    };
+
+    // Input is JSON, there's no outline layout to preserve.
+    io.write_imported(&outline)
+}
+
+/// Read JSON data into the outline form the data mapping reads.
+fn data_outline(text: &str) -> Result<Outline> {
+    let value: Value = serde_json::from_str(text)?;
+    check_representable(&value, "")?;
+    // IDM serializes generic data into the outline form the data mapping
+    // reads.
+    Ok(idm::from_str(&idm::to_string(&value)?)?)
+}
+
+/// Make sure the value has nothing that IDM would silently drop.
+///
+/// IDM has no nulls and empty strings, lists and maps all serialize into
+/// nothing. Object keys must be words that don't read as attributes.
+fn check_representable(value: &Value, path: &str) -> Result<()> {
+    let problem = match value {
+        Value::Null => "null",
+        Value::String(s) if s.is_empty() => "an empty string",
+        Value::Array(a) if a.is_empty() => "an empty list",
+        Value::Object(o) if o.is_empty() => "an empty object",
+        Value::Array(a) => {
+            for (i, v) in a.iter().enumerate() {
+                check_representable(v, &format!("{path}/{i}"))?;
+            }
+            return Ok(());
+        }
+        Value::Object(o) => {
+            for (k, v) in o {
+                // Keys are written as the first word of a line.
+                if k.is_empty()
+                    || k.contains(char::is_whitespace)
+                    || k.starts_with(':')
+                {
+                    let path = if path.is_empty() { "/" } else { path };
+                    bail!(
+                        "JSON key {k:?} at {path} can't be written as IDM, \
+                         keys must be single words that don't start with ':'"
+                    );
+                }
+                check_representable(v, &format!("{path}/{k}"))?;
+            }
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+    let path = if path.is_empty() { "/" } else { path };
+    bail!("JSON value at {path} is {problem}, it can't be written as IDM")
+}
+
+/// JSON form of an outline that maps directly to the outline structure.
+#[derive(Default, Serialize, Deserialize)]
+struct JsonOutline {
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    attrs: IndexMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    children: Vec<JsonSection>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonSection {
+    head: String,
+    #[serde(flatten)]
+    body: JsonOutline,
+}
+
+impl From<Outline> for JsonOutline {
+    fn from(value: Outline) -> Self {
+        JsonOutline {
+            attrs: value.attrs,
+            children: value
+                .children
+                .into_iter()
+                .map(|s| JsonSection {
+                    head: s.head,
+                    body: s.body.into(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<JsonOutline> for Outline {
+    fn from(value: JsonOutline) -> Self {
+        Outline::new(
+            value.attrs,
+            value
+                .children
+                .into_iter()
+                .map(|s| Section::new(s.head, s.body.into()))
+                .collect(),
+        )
+    }
+}
+
+/// Interpret an outline as generic data.
+///
+/// IDM data has no type information, so this guesses at the shape the
+/// outline would deserialize into. Attribute blocks and blocks where every
+/// line starts with a lowercase key word become maps, other blocks become
+/// lists. Scalar values are always strings.
+fn data_value(outline: &Outline) -> Value {
+    let items: Vec<&Section> = outline
+        .children
+        .iter()
+        .filter(|s| {
+            let head = s.head.trim();
+            !(head.is_empty() || head.starts_with("-- ") || head == "--")
+                || !s.body.is_empty()
+        })
+        .collect();
+
+    let mut attrs = Map::new();
+    for (k, v) in &outline.attrs {
+        attrs.insert(k.clone(), text_value(v));
+    }
+
+    if items.is_empty() {
+        return Value::Object(attrs);
+    }
+
+    // Fields of a map are lines that start with a key.
+    let fields: Option<Vec<(&str, Value)>> = items
+        .iter()
+        .map(|s| {
+            let (key, rest) =
+                s.head.split_once(' ').unwrap_or((s.head.as_str(), ""));
+            if !regex!(r"^[a-z_][A-Za-z0-9_-]*$").is_match(key) {
+                return None;
+            }
+            match (rest.trim().is_empty(), s.body.is_empty()) {
+                (true, false) => Some((key, data_value(&s.body))),
+                (false, true) => Some((key, Value::String(rest.into()))),
+                _ => None,
+            }
+        })
+        .collect();
+
+    if let Some(fields) = fields {
+        // Keys must be unique or this isn't a map.
+        let len = fields.len();
+        let mut map = attrs.clone();
+        map.extend(fields.into_iter().map(|(k, v)| (k.to_owned(), v)));
+        if map.len() == attrs.len() + len {
+            return Value::Object(map);
+        }
+    }
+
+    let mut list: Vec<Value> = items
+        .iter()
+        .map(|s| {
+            if s.body.is_empty() {
+                Value::String(s.head.clone())
+            } else if s.head.trim() == "--" {
+                // Separator line for a list of blocks.
+                data_value(&s.body)
+            } else {
+                let mut map = Map::new();
+                map.insert(s.head.clone(), data_value(&s.body));
+                Value::Object(map)
+            }
+        })
+        .collect();
+
+    if attrs.is_empty() {
+        Value::Array(list)
+    } else if items.iter().all(|s| s.head.trim() == "--") {
+        // The first block of a separated block list gets parsed as the
+        // attribute block.
+        list.insert(0, Value::Object(attrs));
+        Value::Array(list)
+    } else {
+        // Outline with both attributes and contents is a pair of the two in
+        // IDM.
+        Value::Array(vec![Value::Object(attrs), Value::Array(list)])
+    }
+}
+
+/// Interpret an attribute value as data.
+fn text_value(text: &str) -> Value {
+    if text.contains('\n') {
+        if let Ok(outline) = idm::from_str::<Outline>(text) {
+            return data_value(&outline);
+        }
+    }
+    Value::String(text.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_data_value() {
+        let outline: Outline = idm::from_str(
+            "\
+name Foo
+tags a b
+nested
+  x 1
+  z
+    q long text here
+list
+  --
+    a 1
+  --
+    a 2",
+        )
+        .unwrap();
+
+        assert_eq!(
+            data_value(&outline),
+            json!({
+                "name": "Foo",
+                "tags": "a b",
+                "nested": { "x": "1", "z": { "q": "long text here" } },
+                "list": [{ "a": "1" }, { "a": "2" }]
+            })
+        );
+
+        let outline: Outline = idm::from_str(
+            "\
+Bookmark
+  :uri https://example.com
+  :tags a b
+Buy milk",
+        )
+        .unwrap();
+
+        assert_eq!(
+            data_value(&outline),
+            json!([
+                { "Bookmark": { "uri": "https://example.com", "tags": "a b" } },
+                "Buy milk"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_data_import() {
+        let outline = data_outline(r#"{"a": "1", "b": ["x", "y"]}"#).unwrap();
+        assert_eq!(idm::to_string(&outline).unwrap(), "a 1\nb x y\n");
+
+        for json in [
+            r#"{"a": null, "b": "x"}"#,
+            r#"{"k": ""}"#,
+            r#"{"k": [1, []]}"#,
+            r#"{"k": {}}"#,
+            r#"{"a b": "c"}"#,
+            r#"{"": "c"}"#,
+            r#"{"k": {":uri": "c"}}"#,
+            "null",
+        ] {
+            assert!(data_outline(json).is_err(), "{json}");
+        }
+    }
+}
@@ -3,7 +3,12 @@ use std::{fs, path::Path};
 use anyhow::{bail, Result};
 
 mod collection;
-pub use collection::{read_directory, write_directory};
+pub use collection::{
+    read_directory, write_directory, write_directory_normalized,
+};
+
+mod document;
+pub use document::Document;
 
 pub mod parse;
 
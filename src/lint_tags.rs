@@ -0,0 +1,373 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use ont::{parse, Outline};
+
+use crate::{tag_aliases::TagAliases, tag_hierarchy::TagHierarchy, IoPipe};
+
+/// Ways to find tags that are probably meant to be the same tag.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Check {
+    /// Tags within a small edit distance of each other.
+    Typo,
+    /// Singular and plural forms, `framework` and `frameworks`.
+    Plural,
+    /// Tags that only differ by hyphens, `web-dev` and `webdev`.
+    Hyphen,
+    /// CamelCase tags and their kebab-case forms, `WebDev` and `web-dev`.
+    Case,
+    /// Tags sharing a first word, `rust`, `rust-async` and `rust-web`.
+    ///
+    /// These are listed without replacements since they're usually
+    /// different tags that might belong in a hierarchy.
+    Prefix,
+}
+
+impl Check {
+    pub const ALL: [Check; 5] = [
+        Check::Typo,
+        Check::Plural,
+        Check::Hyphen,
+        Check::Case,
+        Check::Prefix,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Check::Typo => "typo",
+            Check::Plural => "plural",
+            Check::Hyphen => "hyphen",
+            Check::Case => "case",
+            Check::Prefix => "prefix",
+        }
+    }
+
+    /// Find groups of suspiciously similar tags.
+    pub fn find(self, tags: &BTreeSet<String>) -> Vec<Vec<String>> {
+        if self == Check::Prefix {
+            let mut groups: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+            for t in tags {
+                let prefix = t.split('-').next().unwrap_or(t);
+                groups.entry(prefix).or_default().push(t.clone());
+            }
+            return groups
+                .into_values()
+                .filter(|g| g.len() > 1 && g.iter().any(|t| t.contains('-')))
+                .collect();
+        }
+
+        let mut ret = Vec::new();
+        for (i, a) in tags.iter().enumerate() {
+            for b in tags.iter().skip(i + 1) {
+                if self.is_match(a, b) {
+                    ret.push(vec![a.clone(), b.clone()]);
+                }
+            }
+        }
+        ret
+    }
+
+    fn is_match(self, a: &str, b: &str) -> bool {
+        match self {
+            Check::Typo => {
+                // It's useless for very short tags.
+                if a.len() <= 4 || b.len() <= 4 {
+                    return false;
+                }
+                let distance = levenshtein::levenshtein(a, b);
+                let threshold = match a.len() + b.len() {
+                    x if x < 16 => 1,
+                    _ => 2,
+                };
+                distance <= threshold && distance > 0
+            }
+            Check::Plural => is_plural(a, b) || is_plural(b, a),
+            Check::Hyphen => a.replace('-', "") == b.replace('-', ""),
+            Check::Case => parse::camel_to_kebab(a) == parse::camel_to_kebab(b),
+            Check::Prefix => false,
+        }
+    }
+}
+
+fn is_plural(singular: &str, plural: &str) -> bool {
+    if let Some(stem) = plural.strip_suffix("ies") {
+        if singular.strip_suffix('y') == Some(stem) {
+            return true;
+        }
+    }
+    plural.strip_suffix('s') == Some(singular)
+        || plural.strip_suffix("es") == Some(singular)
+}
+
+pub fn run(
+    io: IoPipe,
+    checks: &[Check],
+    hierarchy: Option<&TagHierarchy>,
+) -> Result<()> {
+    let outline = io.read_outline()?;
+    io.write_text(report(&outline, checks, hierarchy)?)
+}
+
+/// Write the suggested fixes as a replace-tags file.
+fn report(
+    outline: &Outline,
+    checks: &[Check],
+    hierarchy: Option<&TagHierarchy>,
+) -> Result<String> {
+    let aliases = TagAliases::from_outline(outline)?;
+    let checks = if checks.is_empty() {
+        &Check::ALL[..]
+    } else {
+        checks
+    };
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for tag in crate::tags(outline) {
+        *counts.entry(tag).or_default() += 1;
+    }
+    let tags: BTreeSet<String> = counts.keys().cloned().collect();
+
+    // Join tags found similar by any check into clusters that get a single
+    // replacement, remembering which checks found them.
+    let mut clusters = Clusters::default();
+    for alias in tags.iter().filter(|t| aliases.is_alias(t)) {
+        clusters.join(alias, aliases.canonical(alias), "alias");
+    }
+    let mut prefix_groups = Vec::new();
+    for &check in checks {
+        for group in check.find(&tags) {
+            if check == Check::Prefix {
+                prefix_groups.push(group);
+                continue;
+            }
+            for t in &group[1..] {
+                clusters.join(&group[0], t, check.name());
+            }
+        }
+    }
+
+    // Write the clusters as a replace-tags file.
+    let mut out = String::new();
+    let mut replaced = BTreeSet::new();
+    for (checks, cluster) in clusters.clusters() {
+        // Use the canonical alias or the most common tag as the
+        // replacement.
+        let is_canonical = |t: &str| {
+            cluster.iter().any(|a| a != t && aliases.canonical(a) == t)
+        };
+        let target = cluster
+            .iter()
+            .max_by_key(|t| {
+                (
+                    is_canonical(t),
+                    !aliases.is_alias(t),
+                    counts.get(*t).copied().unwrap_or(0),
+                    parse::camel_to_kebab(t) == **t,
+                    Reverse(t.len()),
+                    Reverse(*t),
+                )
+            })
+            .unwrap();
+
+        writeln!(out, "-- {}", checks.join(", "))?;
+        for t in cluster.iter().filter(|t| *t != target) {
+            writeln!(out, "{t} {target}")?;
+            replaced.insert(t.clone());
+        }
+    }
+
+    // Prefix clusters are written as no-op lines for the user to fill in.
+    for group in prefix_groups {
+        let group: Vec<_> = group
+            .into_iter()
+            .filter(|t| !replaced.contains(t))
+            .collect();
+        if group.len() < 2 {
+            continue;
+        }
+        writeln!(out, "-- prefix")?;
+        for t in group {
+            writeln!(out, "{t}")?;
+        }
+    }
+
+    if let Some(hierarchy) = hierarchy {
+        // Suggest the hierarchy tag that most often appears with an
+        // unplaced tag as its parent.
+        let mut cooccurrence: BTreeMap<String, HashMap<String, usize>> =
+            Default::default();
+        for s in outline.content_iter() {
+            let tags = s.explicit_tags();
+            for a in tags.iter().filter(|a| !hierarchy.contains(a)) {
+                let counts = cooccurrence.entry(a.clone()).or_default();
+                for b in tags.iter().filter(|b| hierarchy.contains(b)) {
+                    *counts.entry(b.clone()).or_default() += 1;
+                }
+            }
+        }
+
+        // Comment these out, they're not replacements.
+        if !cooccurrence.is_empty() {
+            writeln!(out, "-- hierarchy")?;
+        }
+        for (tag, counts) in cooccurrence {
+            match counts
+                .into_iter()
+                .max_by_key(|(b, n)| (*n, Reverse(b.clone())))
+            {
+                Some((parent, _)) => {
+                    writeln!(out, "-- {}/{tag}", hierarchy.path(&parent))?
+                }
+                None => writeln!(out, "-- {tag}")?,
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// Disjoint sets of tags.
+#[derive(Default)]
+struct Clusters {
+    parent: BTreeMap<String, String>,
+    checks: BTreeMap<String, Vec<&'static str>>,
+}
+
+impl Clusters {
+    fn root(&self, tag: &str) -> String {
+        let mut tag = tag;
+        while let Some(parent) = self.parent.get(tag) {
+            tag = parent;
+        }
+        tag.to_owned()
+    }
+
+    fn join(&mut self, a: &str, b: &str, check: &'static str) {
+        let (a, b) = (self.root(a), self.root(b));
+        let mut checks = self.checks.remove(&a).unwrap_or_default();
+        if a != b {
+            checks.extend(self.checks.remove(&b).unwrap_or_default());
+            self.parent.insert(b.clone(), a.clone());
+        }
+        if !checks.contains(&check) {
+            checks.push(check);
+        }
+        self.checks.insert(a, checks);
+    }
+
+    /// List the clusters with the checks that joined them.
+    fn clusters(&self) -> Vec<(Vec<&'static str>, Vec<String>)> {
+        let mut members: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for tag in self.parent.keys().chain(self.checks.keys()) {
+            let cluster = members.entry(self.root(tag)).or_default();
+            if !cluster.contains(tag) {
+                cluster.push(tag.clone());
+            }
+        }
+
+        members
+            .into_iter()
+            .map(|(root, mut tags)| {
+                tags.sort();
+                (self.checks[&root].clone(), tags)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(check: Check, tags: &str) -> Vec<Vec<String>> {
+        check.find(&tags.split(' ').map(|t| t.to_owned()).collect())
+    }
+
+    #[test]
+    fn test_checks() {
+        assert_eq!(
+            find(Check::Typo, "color colour"),
+            vec![vec!["color", "colour"]]
+        );
+        assert_eq!(
+            find(
+                Check::Plural,
+                "framework frameworks library libraries box boxes"
+            ),
+            vec![
+                vec!["box", "boxes"],
+                vec!["framework", "frameworks"],
+                vec!["libraries", "library"]
+            ]
+        );
+        assert_eq!(
+            find(Check::Hyphen, "web-dev webdev web"),
+            vec![vec!["web-dev", "webdev"]]
+        );
+        assert_eq!(
+            find(Check::Case, "WebDev web-dev web"),
+            vec![vec!["WebDev", "web-dev"]]
+        );
+        assert_eq!(
+            find(Check::Prefix, "rust rust-async rust-web ruby web"),
+            vec![vec!["rust", "rust-async", "rust-web"]]
+        );
+    }
+
+    #[test]
+    fn test_report_fixes() {
+        let mut outline: Outline = idm::from_str(
+            "\
+A
+  :tags webdev web-dev framework frameworks
+B
+  :tags webdev framework
+C
+  :tags web-dev frameworks",
+        )
+        .unwrap();
+        let rules = crate::replace_tags::Rules::parse(
+            &report(&outline, &[], None).unwrap(),
+        )
+        .unwrap();
+
+        for s in outline.iter_mut() {
+            crate::replace_tags::replace_in(&rules, &mut s.body).unwrap();
+        }
+        let tags: Vec<_> = outline
+            .iter()
+            .map(|s| s.body.attrs["tags"].clone())
+            .collect();
+        assert_eq!(
+            tags,
+            vec!["webdev framework", "webdev framework", "webdev framework"]
+        );
+    }
+
+    #[test]
+    fn test_clusters() {
+        let mut clusters = Clusters::default();
+        clusters.join("webdev", "web-dev", "hyphen");
+        clusters.join("WebDev", "web-dev", "case");
+        clusters.join("color", "colour", "typo");
+        assert_eq!(
+            clusters.clusters(),
+            vec![
+                (
+                    vec!["hyphen", "case"],
+                    vec![
+                        "WebDev".to_owned(),
+                        "web-dev".to_owned(),
+                        "webdev".to_owned()
+                    ]
+                ),
+                (vec!["typo"], vec!["color".to_owned(), "colour".to_owned()]),
+            ]
+        );
+    }
+}
@@ -4,11 +4,13 @@ use std::{
 };
 
 use anyhow::Result;
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 mod io_pipe;
 use io_pipe::IoPipe;
 use ont::Outline;
+use tag_aliases::TagAliases;
+use tag_hierarchy::TagHierarchy;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -22,9 +24,17 @@ enum Commands {
     /// List all links in notes
     AllLinks(IoArgs),
 
-    /// Parse input into IDM and echo it back, use to find unparseable input
-    /// or irregularities that don't survive a roundtrip.
-    Cat(IoArgs),
+    /// Parse input into IDM and echo it back, use to find unparseable input.
+    Cat {
+        /// Rewrite the input in canonical IDM layout instead of preserving
+        /// comments, blank lines and trailing whitespace. Use this to find
+        /// irregularities that don't survive a roundtrip.
+        #[arg(long)]
+        normalize: bool,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
 
     /// Convert a list of rows into a list of columns from those rows.
     Columnize(IoArgs),
@@ -72,8 +82,135 @@ enum Commands {
         io: IoArgs,
     },
 
-    /// Look for suspiciously close tags and mark them as errors.
-    LintTags(IoArgs),
+    /// Import bookmarks from a browser's bookmarks.html export.
+    ImportBookmarksHtml(IoArgs),
+
+    /// Export bookmarks as a bookmarks.html file that browsers can import.
+    ///
+    /// Sections containing bookmarks become bookmark folders.
+    ExportBookmarksHtml(IoArgs),
+
+    /// Import an outline from JSON.
+    ImportJson {
+        /// Read generic JSON data into nested IDM maps and lists instead of
+        /// the outline structure written by `export-json`.
+        #[arg(long)]
+        data: bool,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
+
+    /// Export an outline as JSON.
+    ///
+    /// Sections are written as objects with `head`, `attrs` and `children`
+    /// fields.
+    ExportJson {
+        /// Interpret the outline as generic data with nested maps and lists
+        /// instead of writing out the outline structure.
+        #[arg(long)]
+        data: bool,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
+
+    /// Import a Markdown document as an outline.
+    ///
+    /// Headings and nested lists become sections, front matter becomes
+    /// attributes, link list items become bookmarks and fenced code blocks
+    /// become weave scripts.
+    ImportMarkdown(IoArgs),
+
+    /// Export an outline or a collection as Markdown.
+    ///
+    /// If the output is a directory, each file of the collection is written
+    /// as a separate Markdown file.
+    ExportMarkdown {
+        /// Sections with contents up to this depth become headings, deeper
+        /// ones become nested lists.
+        #[arg(long, default_value = "3")]
+        heading_depth: usize,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
+
+    /// Import an Org document as an outline.
+    ImportOrg(IoArgs),
+
+    /// Export an outline as an Org document.
+    ///
+    /// Sections become headlines, attributes become property drawers and
+    /// weave scripts become source blocks.
+    ExportOrg(IoArgs),
+
+    /// Import an OPML outline or feed list.
+    ImportOpml(IoArgs),
+
+    /// Export an outline as OPML.
+    ///
+    /// Bookmarks with `:type rss` are written as feeds.
+    ExportOpml(IoArgs),
+
+    /// Generate a static HTML site from a collection.
+    ///
+    /// Output must be a directory. Every file of the collection and every
+    /// WikiWord section gets a page, with tag index pages and WikiWord
+    /// cross-links.
+    Site(IoArgs),
+
+    /// List the sections that mention each WikiWord page.
+    ///
+    /// WikiWords are looked for in headlines, attribute values and body
+    /// text.
+    Backlinks {
+        /// Write the backlinks into the wiki pages instead of listing them.
+        #[arg(long, value_enum)]
+        write: Option<wiki::WriteBacklinks>,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
+
+    /// Report WikiWords without a page and wiki pages nothing links to.
+    LintWiki(IoArgs),
+
+    /// Write a graph of wiki pages and tags for visualization.
+    ///
+    /// Edges are WikiWord references, tag memberships and nesting of nodes
+    /// in each other's bodies.
+    Graph {
+        /// Output format.
+        #[arg(long, value_enum, default_value = "dot")]
+        format: graph::Format,
+
+        /// Include sections with an `uri` attribute as nodes.
+        #[arg(long)]
+        bookmarks: bool,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
+
+    /// Look for tags that are probably meant to be the same tag.
+    ///
+    /// The output is a replacement file for `replace-tags`. Review it before
+    /// use, the most common tag of each group is used as the replacement.
+    /// With a tag hierarchy, tags missing from the hierarchy are listed with
+    /// suggested places for them.
+    LintTags {
+        /// Checks to run, comma-separated, defaults to all of them.
+        #[arg(long, value_enum, value_delimiter = ',')]
+        check: Vec<lint_tags::Check>,
+
+        /// Tag hierarchy file, an outline of tags with subtags as children.
+        #[arg(long)]
+        hierarchy: Option<PathBuf>,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
 
     /// List all tags in a collection.
     ListTags {
@@ -82,6 +219,11 @@ enum Commands {
         #[arg(long)]
         histogram: bool,
 
+        /// Tag hierarchy file, histogram counts of tags include their
+        /// subtags.
+        #[arg(long)]
+        hierarchy: Option<PathBuf>,
+
         #[command(flatten)]
         io: IoArgs,
     },
@@ -96,6 +238,11 @@ enum Commands {
         #[arg(required = true)]
         new: String,
 
+        /// If a wiki page has the old tag as its implicit tag, rename the
+        /// page and the WikiWord mentions of it to match the new tag.
+        #[arg(long)]
+        wiki: bool,
+
         #[command(flatten)]
         io: IoArgs,
     },
@@ -105,13 +252,56 @@ enum Commands {
         /// Tag replacement list, a file with lines with format `old1-tag
         /// new1-tag` or `old2-tag new2-tag new3-tag ...`. The old tags will
         /// be removed and all the new tags will be added. Lines with just a
-        /// single tag name in the replacements file do nothing.
+        /// single tag name in the replacements file do nothing and lines
+        /// like `old-tag -` delete the tag. The old tag can be a regular
+        /// expression between slashes, `/^imported-(.*)$/ $1`.
         replacements: PathBuf,
 
+        /// Only replace tags of items matching this tag expression, like
+        /// the ones for `tagged`.
+        #[arg(long, value_name = "EXPR")]
+        tagged: Option<String>,
+
+        /// If a wiki page has a replaced tag as its implicit tag and the rule
+        /// has a single new tag, rename the page and the WikiWord mentions of
+        /// it to match the new tag.
+        #[arg(long)]
+        wiki: bool,
+
         #[command(flatten)]
         io: IoArgs,
     },
 
+    /// Search for sections matching all the query terms, best matches first.
+    ///
+    /// Results are written with their ancestor headlines. Headlines and tags
+    /// weigh more than attributes and body text.
+    Search {
+        // A positional input argument is mandatory for this command so we
+        // spell out a variant of IoArgs.
+        /// Input file path, use '-' for stdin.
+        #[arg(required = true)]
+        input: PathBuf,
+
+        /// Search terms. Write `prefix*` to match words starting with prefix
+        /// and put phrases in double quotes.
+        #[arg(required = true)]
+        query: Vec<String>,
+
+        /// Maximum number of results.
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+
+        /// Save the search index in this file and reuse it while the input
+        /// stays unchanged.
+        #[arg(long)]
+        index: Option<PathBuf>,
+
+        /// Output file path, defaults to stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
     /// Filter items that already exist in the collection out of the input.
     SortBy {
         /// Field to sort lexically by.
@@ -126,6 +316,39 @@ enum Commands {
         io: IoArgs,
     },
 
+    /// Suggest tags for entries without tags based on the tagged ones.
+    ///
+    /// Entries are sections with attributes, like bookmarks. The
+    /// suggestions come from a naive Bayes model of the words and URL
+    /// domains of the tagged sections in the input.
+    SuggestTags {
+        /// Maximum number of tags to suggest for an entry.
+        #[arg(long, default_value = "3")]
+        max: usize,
+
+        /// Write the suggestions into `:suggested-tags` attributes of the
+        /// entries instead of listing them.
+        #[arg(long)]
+        write: bool,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
+
+    /// Print tag statistics as tables that can be formatted with `tf`.
+    TagStats {
+        /// Which table to print.
+        #[arg(long, value_enum, default_value = "cooccurrence")]
+        report: tag_stats::Report,
+
+        /// How many of the most common tags to include in the tables.
+        #[arg(long, default_value = "20")]
+        top: usize,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
+
     /// Find items with specific tags.
     Tagged {
         // A positional input argument is mandatory for this command so we
@@ -139,7 +362,15 @@ enum Commands {
         #[arg(long)]
         flatten: bool,
 
-        /// List of tags that must be present in items returned.
+        /// Tag hierarchy file, items tagged with a subtag match its parent
+        /// tags.
+        #[arg(long)]
+        hierarchy: Option<PathBuf>,
+
+        /// Tags that must be present in items returned.
+        ///
+        /// Use `|` for alternatives, `!` to exclude a tag and parentheses for
+        /// grouping, eg. `rust '(async | tokio)' '!deprecated'`.
         #[arg(required = true)]
         tag_list: Vec<String>,
 
@@ -148,6 +379,33 @@ enum Commands {
         output: Option<PathBuf>,
     },
 
+    /// Write copies of the sections matching named tag queries into view
+    /// outlines.
+    Views {
+        /// View definition file with lines with a view name followed by a
+        /// tag query, `rust-reading rust !read`.
+        #[arg(required = true)]
+        views: PathBuf,
+
+        /// Tag hierarchy file, items tagged with a subtag match its parent
+        /// tags.
+        #[arg(long)]
+        hierarchy: Option<PathBuf>,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
+
+    /// Write the sections edited in views back into the input.
+    SyncViews {
+        /// Views written earlier by `views`, a file or a directory.
+        #[arg(required = true)]
+        views: PathBuf,
+
+        #[command(flatten)]
+        io: IoArgs,
+    },
+
     /// Format table columns.
     Tf {
         /// Treat every column as left-aligned textual data, don't try to
@@ -210,12 +468,17 @@ fn main() -> Result<()> {
                 }
             }
 
-            io.write(&out)
+            io.write_normalized(&out)
         }
 
-        Cat(args) => {
-            let io = IoPipe::try_from(args)?;
-            io.write(&io.read_outline()?)
+        Cat { normalize, io } => {
+            let io = IoPipe::try_from(io)?;
+            let outline = io.read_outline()?;
+            if normalize {
+                io.write_normalized(&outline)
+            } else {
+                io.write(&outline)
+            }
         }
 
         Columnize(args) => columnize::run(args.try_into()?),
@@ -236,18 +499,61 @@ fn main() -> Result<()> {
 
         Weave { force, io } => weave::run(force, io.try_into()?),
 
+        SuggestTags { max, write, io } => {
+            suggest_tags::run(io.try_into()?, max, write)
+        }
+
+        TagStats { report, top, io } => {
+            tag_stats::run(io.try_into()?, report, top)
+        }
+
         Tagged {
             input,
             flatten,
+            hierarchy,
             tag_list,
             output,
         } => {
             let io = IoArgs {
-                input,
+                input: vec![input],
+                merge: Merge::default(),
+                at: None,
+                lines: None,
                 in_place: false,
                 output,
             };
-            tagged::run(io.try_into()?, tag_list, flatten)
+            let hierarchy = load_hierarchy(hierarchy)?;
+            tagged::run(io.try_into()?, tag_list, flatten, &hierarchy)
+        }
+
+        Views {
+            views,
+            hierarchy,
+            io,
+        } => {
+            let views = views::Views::load(views)?;
+            let hierarchy = load_hierarchy(hierarchy)?;
+            views::run(io.try_into()?, &views, &hierarchy)
+        }
+
+        SyncViews { views, io } => views::sync(io.try_into()?, views),
+
+        Search {
+            input,
+            query,
+            limit,
+            index,
+            output,
+        } => {
+            let io = IoArgs {
+                input: vec![input],
+                merge: Merge::default(),
+                at: None,
+                lines: None,
+                in_place: false,
+                output,
+            };
+            search::run(io.try_into()?, &query, limit, index.as_deref())
         }
 
         ImportRaindrop {
@@ -259,75 +565,96 @@ fn main() -> Result<()> {
             raindrop::export(io.try_into()?, folder)
         }
 
-        RenameTag { old, new, io } => {
-            replace_tags::rename(io.try_into()?, old, new)
-        }
+        ImportBookmarksHtml(io) => bookmarks_html::import(io.try_into()?),
+
+        ExportBookmarksHtml(io) => bookmarks_html::export(io.try_into()?),
+
+        ImportJson { data, io } => json::import(io.try_into()?, data),
+
+        ExportJson { data, io } => json::export(io.try_into()?, data),
+
+        ImportMarkdown(io) => markdown::import(io.try_into()?),
 
-        ReplaceTags { replacements, io } => {
-            replace_tags::replace(io.try_into()?, replacements)
+        ExportMarkdown { heading_depth, io } => {
+            markdown::export(io.try_into()?, heading_depth)
         }
 
-        LintTags(args) => {
-            let io = IoPipe::try_from(args)?;
-            let outline = io.read_outline()?;
-            let tags = tags(&outline).collect::<BTreeSet<String>>();
-
-            // Collect sets of suspiciously similar tags.
-            let mut typos: HashMap<String, Vec<String>> = HashMap::new();
-            for (i, a) in tags.iter().enumerate() {
-                for b in tags.iter().skip(i + 1) {
-                    // It's useless for very short tags.
-                    if a.len() <= 4 || b.len() <= 4 {
-                        continue;
-                    }
-                    let distance = levenshtein::levenshtein(a, b);
-                    let threshold = match a.len() + b.len() {
-                        x if x < 16 => 1,
-                        _ => 2,
-                    };
-                    if distance <= threshold && distance > 0 {
-                        typos.entry(a.clone()).or_default().push(b.clone());
-                        typos.entry(b.clone()).or_default().push(a.clone());
-                    }
-                }
-            }
+        ImportOrg(io) => org::import(io.try_into()?),
 
-            // Deduplicate.
-            let typos: BTreeSet<Vec<String>> = typos
-                .into_iter()
-                .map(|(k, v)| {
-                    let mut v = v;
-                    v.push(k);
-                    v.sort();
-                    v
-                })
-                .collect();
-
-            // Sort by longest (most likely to be an actual typo) last.
-            let mut typos: Vec<Vec<String>> = typos.into_iter().collect();
-            typos.sort_by_key(|v| v[0].len());
-
-            for seq in &typos {
-                for w in seq {
-                    print!("{} ", w);
-                }
-                println!();
-            }
+        ExportOrg(io) => org::export(io.try_into()?),
+
+        ImportOpml(io) => opml::import(io.try_into()?),
+
+        ExportOpml(io) => opml::export(io.try_into()?),
+
+        Site(io) => site::run(io.try_into()?),
+
+        Backlinks { write, io } => wiki::backlinks(io.try_into()?, write),
+
+        LintWiki(io) => wiki::lint(io.try_into()?),
+
+        Graph {
+            format,
+            bookmarks,
+            io,
+        } => graph::run(io.try_into()?, format, bookmarks),
 
-            Ok(())
+        RenameTag {
+            old,
+            new,
+            wiki,
+            io,
+        } => replace_tags::rename(io.try_into()?, old, new, wiki),
+
+        ReplaceTags {
+            replacements,
+            tagged,
+            wiki,
+            io,
+        } => {
+            let scope =
+                tagged.as_deref().map(tagged::TagExpr::parse).transpose()?;
+            replace_tags::replace(
+                io.try_into()?,
+                replacements,
+                scope.as_ref(),
+                wiki,
+            )
         }
 
-        ListTags { histogram, io } => {
+        LintTags {
+            check,
+            hierarchy,
+            io,
+        } => {
+            let hierarchy = hierarchy.map(TagHierarchy::load).transpose()?;
+            lint_tags::run(io.try_into()?, &check, hierarchy.as_ref())
+        }
+
+        ListTags {
+            histogram,
+            hierarchy,
+            io,
+        } => {
             use std::fmt::Write;
 
+            let hierarchy = load_hierarchy(hierarchy)?;
             let io = IoPipe::try_from(io)?;
             let outline = io.read_outline()?;
+            let aliases = TagAliases::from_outline(&outline)?;
 
             let mut out = String::new();
             if histogram {
                 let mut counts: HashMap<String, usize> = HashMap::new();
-                for tag in tags(&outline) {
-                    *counts.entry(tag).or_default() += 1;
+                for s in outline.content_iter() {
+                    // Count items with subtags for the parent tags too.
+                    let mut tags: BTreeSet<String> =
+                        s.explicit_tags().into_iter().collect();
+                    aliases.canonicalize(&mut tags);
+                    hierarchy.expand(&mut tags);
+                    for tag in tags {
+                        *counts.entry(tag).or_default() += 1;
+                    }
                 }
                 let mut counts: Vec<(String, usize)> =
                     counts.into_iter().collect();
@@ -342,7 +669,8 @@ fn main() -> Result<()> {
                     writeln!(out, "{:width$}  {}", tag, count)?;
                 }
             } else {
-                let tags = tags(&outline).collect::<BTreeSet<String>>();
+                let mut tags = tags(&outline).collect::<BTreeSet<String>>();
+                aliases.canonicalize(&mut tags);
                 for tag in tags {
                     writeln!(out, "{}", tag)?;
                 }
@@ -361,15 +689,30 @@ fn main() -> Result<()> {
     }
 }
 
+mod bookmarks_html;
 mod columnize;
 mod filter_existing;
 mod find_dupes;
+mod graph;
+mod json;
+mod lint_tags;
+mod markdown;
+mod opml;
+mod org;
 mod raindrop;
 mod replace_tags;
+mod search;
+mod site;
 mod sort_by;
+mod suggest_tags;
+mod tag_aliases;
+mod tag_hierarchy;
+mod tag_stats;
 mod tagged;
 mod tf;
+mod views;
 mod weave;
+mod wiki;
 
 /// Standard input/output specification for subcommands.
 ///
@@ -377,9 +720,28 @@ mod weave;
 /// allows pointing to files instead.
 #[derive(Debug, Args, Clone)]
 pub struct IoArgs {
-    /// Input file path, defaults to stdin.
+    /// Input file paths, defaults to stdin.
+    ///
+    /// Multiple inputs are combined into a single outline.
     #[arg(default_value = "-")]
-    input: PathBuf,
+    input: Vec<PathBuf>,
+
+    /// How to combine multiple inputs.
+    #[arg(long, value_enum, default_value = "concat")]
+    merge: Merge,
+
+    /// Only process the body of the section at this slash-separated
+    /// headline path, leaving the rest of the input unchanged.
+    ///
+    /// For collections, the path starts with the subdirectories and the file
+    /// name.
+    #[arg(long, conflicts_with = "lines")]
+    at: Option<String>,
+
+    /// Only process the lines N:M (one-based, inclusive) of the input,
+    /// leaving the rest of the input unchanged.
+    #[arg(long, value_name = "N:M")]
+    lines: Option<String>,
 
     /// Whether to modify a file or collection in-place with the tool.
     ///
@@ -388,19 +750,33 @@ pub struct IoArgs {
     in_place: bool,
 
     /// Output file path, defaults to stdout.
+    ///
+    /// Use '-' to explicitly write to stdout, large outlines read from a
+    /// collection will not be printed to a terminal otherwise. End the path
+    /// with '/' to write a new collection directory.
     #[arg(short, long)]
     output: Option<PathBuf>,
-    // XXX Using Option here instead of just setting it to default to "-" so
-    // that we can differentiate between the user explicitly asking for stdout
-    // output or just writing minimal calls that might blast a whole
-    // collection to stdout. Haven't bothered to implement that yet though.
+}
+
+/// Ways to combine multiple input outlines into one.
+#[derive(Copy, Clone, Debug, Default, ValueEnum)]
+pub enum Merge {
+    /// Append the contents of the inputs one after another.
+    #[default]
+    Concat,
+    /// Put the contents of each input under a section named after its path.
+    Wrap,
+    /// Combine sections with identical headlines.
+    Head,
+}
+
+fn load_hierarchy(path: Option<PathBuf>) -> Result<TagHierarchy> {
+    Ok(path
+        .map(TagHierarchy::load)
+        .transpose()?
+        .unwrap_or_default())
 }
 
 fn tags(outline: &Outline) -> impl Iterator<Item = String> + '_ {
-    outline.iter().flat_map(|s| {
-        s.body
-            .get::<Vec<String>>("tags")
-            .unwrap_or_default()
-            .unwrap_or_default()
-    })
+    outline.content_iter().flat_map(|s| s.explicit_tags())
 }
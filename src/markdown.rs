@@ -0,0 +1,849 @@
+use std::{fmt::Write, path::PathBuf};
+
+use anyhow::Result;
+use indexmap::IndexMap;
+use lazy_regex::regex;
+use ont::{parse, Outline, Section};
+
+use crate::{
+    weave::{anonymous_script, script_language, weave_filename, OUTPUT_MARKER},
+    IoPipe,
+};
+
+pub fn export(io: IoPipe, heading_depth: usize) -> Result<()> {
+    let outline = io.read_outline()?;
+
+    if io.is_dest_collection() {
+        // Mirror the collection structure with a Markdown file for each
+        // outline file.
+        let mut files = Vec::new();
+        collect_files(&mut files, PathBuf::new(), &outline, heading_depth)?;
+        io.write_files(files)
+    } else {
+        io.write_text(to_markdown(&outline, heading_depth)?)
+    }
+}
+
+pub fn import(io: IoPipe) -> Result<()> {
+    let outline = from_markdown(&io.read_text()?);
+
+    // Input is Markdown, there's no outline layout to preserve.
+    io.write_imported(&outline)
+}
+
+fn collect_files(
+    files: &mut Vec<(PathBuf, String)>,
+    dir: PathBuf,
+    outline: &Outline,
+    heading_depth: usize,
+) -> Result<()> {
+    for s in &outline.children {
+        if s.head.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(name) = s.head.strip_suffix('/') {
+            collect_files(files, dir.join(name), &s.body, heading_depth)?;
+        } else if s.head.ends_with(':') {
+            // Not an outline file.
+            log::info!("export_markdown: Skipping non-IDM file {:?}", s.head);
+        } else {
+            files.push((
+                dir.join(format!("{}.md", s.head)),
+                to_markdown(&s.body, heading_depth)?,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Render an outline as a Markdown document.
+///
+/// Sections with contents less than `heading_depth` levels deep become
+/// headings, everything else becomes nested lists. Attributes of the
+/// toplevel outline are written as YAML front matter.
+pub fn to_markdown(outline: &Outline, heading_depth: usize) -> Result<String> {
+    let mut out = String::new();
+
+    if !outline.attrs.is_empty() {
+        writeln!(out, "---")?;
+        for (k, v) in &outline.attrs {
+            write_yaml_field(&mut out, k, v)?;
+        }
+        writeln!(out, "---")?;
+        writeln!(out)?;
+    }
+
+    let mut md = Markdown {
+        out,
+        heading_depth,
+        in_list: false,
+    };
+    md.block(&outline.children, 0, None)?;
+
+    // Tidy up the paragraph separation.
+    let mut ret = md.out.trim().to_owned();
+    ret.push('\n');
+    Ok(ret)
+}
+
+struct Markdown {
+    out: String,
+    heading_depth: usize,
+    /// Whether the last thing written was a toplevel list.
+    in_list: bool,
+}
+
+impl Markdown {
+    /// Write the sections of a block.
+    ///
+    /// `list_indent` is the indentation of the list items if we're inside
+    /// a list.
+    fn block(
+        &mut self,
+        sections: &[Section],
+        depth: usize,
+        list_indent: Option<&str>,
+    ) -> Result<()> {
+        let mut i = 0;
+        while i < sections.len() {
+            let s = &sections[i];
+            i += 1;
+            let head = s.head.trim();
+
+            if head.is_empty() {
+                // Blank lines separate paragraphs outside lists.
+                if list_indent.is_none() {
+                    self.paragraph_break();
+                }
+                continue;
+            }
+
+            if head == "--" || head.starts_with("-- ") {
+                // Comment lines are dropped, but a comment can head an
+                // indented block.
+                self.block(&s.body.children, depth, list_indent)?;
+                continue;
+            }
+
+            if let Some(name) = weave_filename(head) {
+                self.code(
+                    list_indent,
+                    &script_language(name, &s.body),
+                    &s.body,
+                )?;
+
+                if let Some(output) =
+                    sections.get(i).filter(|o| o.head.trim() == OUTPUT_MARKER)
+                {
+                    self.code(list_indent, "text", &output.body)?;
+                    i += 1;
+                }
+                continue;
+            }
+
+            if list_indent.is_none()
+                && depth < self.heading_depth
+                && !s.body.children.is_empty()
+            {
+                self.paragraph_break();
+                writeln!(self.out, "{} {}", "#".repeat(depth + 1), title(s))?;
+                writeln!(self.out)?;
+
+                // Definition list for the attributes.
+                for (k, v) in attrs(s) {
+                    writeln!(self.out, "{k}")?;
+                    for (j, line) in v.lines().enumerate() {
+                        let marker = if j == 0 { ":" } else { " " };
+                        writeln!(self.out, "{marker}   {line}")?;
+                    }
+                    writeln!(self.out)?;
+                }
+
+                self.block(&s.body.children, depth + 1, None)?;
+                continue;
+            }
+
+            let indent = list_indent.unwrap_or("");
+            if list_indent.is_none() && !self.in_list {
+                // Start a new list.
+                self.paragraph_break();
+            }
+            writeln!(self.out, "{indent}- {}", title(s))?;
+
+            let child_indent = format!("{indent}  ");
+            for (k, v) in attrs(s) {
+                writeln!(
+                    self.out,
+                    "{child_indent}- *{k}*: {}",
+                    v.lines().collect::<Vec<_>>().join(" ")
+                )?;
+            }
+            self.block(&s.body.children, depth + 1, Some(&child_indent))?;
+            if list_indent.is_none() {
+                self.in_list = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a fenced code block from the text lines of a section body.
+    fn code(
+        &mut self,
+        list_indent: Option<&str>,
+        lang: &str,
+        body: &Outline,
+    ) -> Result<()> {
+        // Only the lines are code, attributes are weave metadata.
+        let text = idm::to_string(&Outline::from_iter(body.children.clone()))?;
+        let fence = if text.contains("```") { "~~~" } else { "```" };
+        let indent = list_indent.unwrap_or("");
+
+        if list_indent.is_none() {
+            self.paragraph_break();
+        }
+        writeln!(self.out, "{indent}{fence}{lang}")?;
+        for line in text.lines() {
+            if line.trim().is_empty() {
+                writeln!(self.out)?;
+            } else {
+                writeln!(self.out, "{indent}{line}")?;
+            }
+        }
+        writeln!(self.out, "{indent}{fence}")?;
+        if list_indent.is_none() {
+            self.paragraph_break();
+        }
+        Ok(())
+    }
+
+    fn paragraph_break(&mut self) {
+        self.in_list = false;
+        if !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+    }
+}
+
+/// Headline text with links and emphasis applied.
+fn title(s: &Section) -> String {
+    let text = parse::important(&s.head).unwrap_or(&s.head).trim();
+
+    let text = match s.body.attrs.get("uri") {
+        Some(uri) => format!("[{text}]({uri})"),
+        None => text.to_owned(),
+    };
+
+    if s.is_important() {
+        format!("**{text}**")
+    } else {
+        text
+    }
+}
+
+/// Section attributes that aren't shown in the title.
+fn attrs(s: &Section) -> impl Iterator<Item = (&String, &String)> {
+    s.body.attrs.iter().filter(|(k, _)| *k != "uri")
+}
+
+/// Write a key-value pair as YAML front matter.
+fn write_yaml_field(out: &mut String, key: &str, value: &str) -> Result<()> {
+    if key == "tags" {
+        // Tags are a list in front matter conventions.
+        let tags: Vec<String> = idm::from_str(value).unwrap_or_default();
+        writeln!(out, "tags: [{}]", tags.join(", "))?;
+    } else if value.contains('\n') {
+        writeln!(out, "{key}: |")?;
+        for line in value.lines() {
+            writeln!(out, "  {line}")?;
+        }
+    } else if needs_quoting(value) {
+        // JSON strings are valid YAML.
+        writeln!(out, "{key}: {}", serde_json::to_string(value)?)?;
+    } else {
+        writeln!(out, "{key}: {value}")?;
+    }
+    Ok(())
+}
+
+fn needs_quoting(value: &str) -> bool {
+    value.is_empty()
+        || value.trim() != value
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.starts_with(|c| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+}
+
+/// Parse a Markdown document into an outline.
+///
+/// This reads back what `to_markdown` writes. Headings and list items become
+/// nested sections, front matter becomes the attributes of the outline, list
+/// items that are links become bookmarks and fenced code blocks become weave
+/// scripts. Other Markdown is kept as lines of text.
+pub fn from_markdown(text: &str) -> Outline {
+    let mut lines: Vec<&str> = text.lines().collect();
+
+    let mut importer = Importer {
+        stack: vec![(Level::Root, Section::default())],
+        blank: false,
+        attr: None,
+        after_code: false,
+    };
+
+    // Front matter.
+    if lines.first().is_some_and(|a| a.trim_end() == "---") {
+        if let Some(end) = lines
+            .iter()
+            .skip(1)
+            .position(|a| matches!(a.trim_end(), "---" | "..."))
+        {
+            importer.stack[0].1.body.attrs = front_matter(&lines[1..end + 1]);
+            lines.drain(..end + 2);
+        }
+    }
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+
+        let indent = parse::indentation(line).len();
+        let text = line.trim();
+        if text.is_empty() {
+            importer.blank = true;
+            continue;
+        }
+
+        let attr = importer.attr.take();
+        let after_code = std::mem::take(&mut importer.after_code);
+
+        if let Some(caps) = regex!(r"^(```+|~~~+)\s*([^`\s]*)").captures(text) {
+            // Fenced code block, runs until a closing fence at least as long
+            // as the opening one.
+            let fence = &caps[1];
+            let mut code = Vec::new();
+            while i < lines.len() {
+                let line = lines[i];
+                i += 1;
+                let close = line.trim();
+                if close.starts_with(fence)
+                    && close.trim_start_matches(&fence[..1]).is_empty()
+                {
+                    break;
+                }
+                // Remove the fence's indentation from the code.
+                let n = parse::indentation(line).len().min(indent);
+                code.push(&line[n..]);
+            }
+            importer.code(indent, &caps[2], &code, after_code);
+            continue;
+        }
+
+        if let Some(caps) =
+            regex!(r"^(#{1,6})(\s+(.*?))??(\s+#+)?$").captures(text)
+        {
+            let title = caps.get(3).map_or("", |m| m.as_str());
+            importer.heading(caps[1].len(), title);
+            continue;
+        }
+
+        if regex!(r"^([-*_])(\s*[-*_]){2,}$").is_match(text) {
+            // Thematic break, ends any lists.
+            importer.close_items();
+            importer.blank = true;
+            continue;
+        }
+
+        if let Some(caps) =
+            regex!(r"^([-*+]|\d{1,9}[.)])(\s+(.*))?$").captures(text)
+        {
+            let item = caps.get(3).map_or("", |m| m.as_str());
+            importer.item(indent, item);
+            continue;
+        }
+
+        if let Some(value) = text.strip_prefix(':') {
+            if value.starts_with(char::is_whitespace)
+                && importer.definition(value.trim())
+            {
+                continue;
+            }
+        }
+
+        if let Some(key) = attr.filter(|_| indent > 0 && !importer.blank) {
+            // Continuation of a multi-line definition.
+            let value = importer.top().body.attrs.entry(key.clone());
+            let value = value.or_default();
+            value.push('\n');
+            value.push_str(text);
+            importer.attr = Some(key);
+            continue;
+        }
+
+        importer.text(indent, text);
+    }
+
+    while importer.stack.len() > 1 {
+        importer.close();
+    }
+    importer.stack.pop().unwrap().1.body
+}
+
+/// Nesting level of an open section in a Markdown document.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+enum Level {
+    Root,
+    /// Heading with the number of `#` characters.
+    Heading(usize),
+    /// List item with the indentation of the item marker.
+    Item(usize),
+}
+
+struct Importer {
+    /// Sections that can still get more content, innermost last.
+    stack: Vec<(Level, Section)>,
+    /// Whether there were blank lines after the last content.
+    blank: bool,
+    /// Attribute set by the last definition list line.
+    attr: Option<String>,
+    /// Whether the last content was a code block.
+    after_code: bool,
+}
+
+impl Importer {
+    fn top(&mut self) -> &mut Section {
+        &mut self.stack.last_mut().unwrap().1
+    }
+
+    fn level(&self) -> Level {
+        self.stack.last().unwrap().0
+    }
+
+    /// Finish the innermost open section.
+    fn close(&mut self) {
+        let (_, section) = self.stack.pop().unwrap();
+        self.top().body.push(section);
+    }
+
+    fn close_items(&mut self) {
+        while matches!(self.level(), Level::Item(_)) {
+            self.close();
+        }
+    }
+
+    /// Close list items that content at the given indentation isn't a part
+    /// of.
+    fn close_to(&mut self, indent: usize) {
+        while matches!(self.level(), Level::Item(i) if indent <= i) {
+            self.close();
+        }
+    }
+
+    /// Add content to the innermost open section.
+    fn add(&mut self, section: Section) {
+        self.separate();
+        self.top().body.push(section);
+    }
+
+    /// Add a blank line before new content if the source had one.
+    fn separate(&mut self) {
+        if std::mem::take(&mut self.blank)
+            && !self.top().body.children.is_empty()
+        {
+            self.top().body.push_line("");
+        }
+    }
+
+    fn open(&mut self, level: Level, section: Section) {
+        while self.level() >= level && self.level() != Level::Root {
+            self.close();
+        }
+        self.stack.push((level, section));
+    }
+
+    fn heading(&mut self, depth: usize, title: &str) {
+        self.blank = false;
+        self.open(Level::Heading(depth), section(title));
+    }
+
+    fn item(&mut self, indent: usize, text: &str) {
+        self.close_to(indent);
+
+        // Attribute lines written by `to_markdown`.
+        if let Some(caps) =
+            regex!(r"^\*([a-z][a-z0-9-]*)\*: (.*)$").captures(text)
+        {
+            if matches!(self.level(), Level::Item(_))
+                && self.top().body.children.is_empty()
+            {
+                self.blank = false;
+                self.top().body.attrs.insert(caps[1].into(), caps[2].into());
+                return;
+            }
+        }
+
+        self.separate();
+        self.open(Level::Item(indent), section(text));
+    }
+
+    /// Turn the preceding line into a definition list attribute.
+    fn definition(&mut self, value: &str) -> bool {
+        if self.blank {
+            return false;
+        }
+        let top = self.top();
+        let Some(key) = top.body.children.last().and_then(|s| {
+            (s.body.is_empty()
+                && regex!(r"^[A-Za-z][A-Za-z0-9_ -]*$").is_match(&s.head))
+            .then(|| attr_name(&s.head))
+        }) else {
+            return false;
+        };
+
+        top.body.children.pop();
+        top.body.attrs.insert(key.clone(), value.into());
+        self.attr = Some(key);
+        true
+    }
+
+    fn text(&mut self, indent: usize, text: &str) {
+        // Lazy continuation of a list item's text.
+        if !self.blank
+            && matches!(self.level(), Level::Item(_))
+            && self.top().body.is_empty()
+        {
+            let head = &mut self.top().head;
+            let important = parse::important(head).map(|h| h.to_owned());
+            match important {
+                Some(h) => *head = format!("{h} {text} *"),
+                None => {
+                    head.push(' ');
+                    head.push_str(text);
+                }
+            }
+            return;
+        }
+
+        self.close_to(indent);
+        self.add(Section::new(escape_colon(text), Outline::default()));
+    }
+
+    fn code(
+        &mut self,
+        indent: usize,
+        lang: &str,
+        code: &[&str],
+        after_code: bool,
+    ) {
+        self.close_to(indent);
+
+        let body = text_outline(code);
+        if lang == "text" && after_code {
+            // Output block of the preceding script.
+            self.blank = false;
+            self.add(Section::new(OUTPUT_MARKER, body));
+        } else {
+            self.add(Section::new(anonymous_script(lang), body));
+        }
+        self.after_code = true;
+    }
+}
+
+/// Section for a heading or a list item.
+fn section(text: &str) -> Section {
+    let mut head = text.trim();
+
+    let important = regex!(r"^\*\*([^*]|[^*].*[^*])\*\*$").is_match(head);
+    if important {
+        head = &head[2..head.len() - 2];
+    }
+
+    let mut body = Outline::default();
+    if let Some(caps) =
+        regex!(r"^\[([^\]]*)\]\(<?([^\s>]+)>?\)$").captures(head)
+    {
+        body.attrs.insert("uri".into(), caps[2].into());
+        head = caps.get(1).unwrap().as_str();
+    }
+
+    let head = escape_colon(head);
+    if important {
+        Section::new(format!("{head} *"), body)
+    } else {
+        Section::new(head, body)
+    }
+}
+
+/// Escape a line that IDM would read as an attribute, `:uri x` becomes
+/// `\:uri x`, which Markdown still shows as `:uri x`.
+fn escape_colon(text: &str) -> String {
+    if regex!(r"^:\S").is_match(text) {
+        format!("\\{text}")
+    } else {
+        text.to_owned()
+    }
+}
+
+/// Read lines of text as an outline.
+///
+/// Lines that don't have consistent indentation are read as a flat list.
+pub fn text_outline(lines: &[impl AsRef<str>]) -> Outline {
+    // Remove indentation shared by all the lines.
+    let n = lines
+        .iter()
+        .map(|a| a.as_ref())
+        .filter(|a| !a.trim().is_empty())
+        .map(|a| parse::indentation(a).len())
+        .min()
+        .unwrap_or(0);
+    let text = lines
+        .iter()
+        .map(|a| a.as_ref().get(n..).unwrap_or(""))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let text = text.trim_start_matches('\n').trim_end();
+    if text.is_empty() {
+        return Outline::default();
+    }
+
+    match idm::from_str::<Outline>(text) {
+        // Lines that look like attributes can't be stored as text.
+        Ok(outline) if outline.attrs.is_empty() => outline,
+        _ => {
+            // Irregular indentation, flatten the lines.
+            log::warn!("text_outline: Flattening text block {text:?}");
+            let mut outline = Outline::default();
+            for line in text.lines() {
+                outline.push_line(line.trim());
+            }
+            outline
+        }
+    }
+}
+
+/// Attribute name from a Markdown or YAML field name.
+fn attr_name(key: &str) -> String {
+    key.trim().to_lowercase().replace([' ', '_'], "-")
+}
+
+/// Read a simple YAML front matter block into attributes.
+///
+/// Supports scalar values, lists and `|` and `>` text blocks. A `tags` list
+/// becomes the space-separated tag list IDM uses.
+fn front_matter(lines: &[&str]) -> IndexMap<String, String> {
+    let mut attrs = IndexMap::new();
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        if line.starts_with(char::is_whitespace) || line.starts_with('#') {
+            continue;
+        }
+        let key = attr_name(key);
+        let value = value.trim();
+
+        // Indented lines after the key.
+        let start = i;
+        while i < lines.len()
+            && (lines[i].trim().is_empty()
+                || lines[i].starts_with(char::is_whitespace)
+                || lines[i].starts_with("- "))
+        {
+            i += 1;
+        }
+        let block: Vec<&str> =
+            lines[start..i].iter().map(|a| a.trim()).collect();
+
+        let items: Vec<String> = if let Some(list) =
+            value.strip_prefix('[').and_then(|v| v.strip_suffix(']'))
+        {
+            list.split(',')
+                .map(yaml_scalar)
+                .filter(|a| !a.is_empty())
+                .collect()
+        } else if value.is_empty() {
+            block
+                .iter()
+                .filter_map(|a| a.strip_prefix('-'))
+                .map(yaml_scalar)
+                .collect()
+        } else if value.starts_with(['|', '>']) {
+            let sep = if value.starts_with('|') { "\n" } else { " " };
+            let text = block.join(sep);
+            attrs.insert(key, text.trim().to_owned());
+            continue;
+        } else {
+            attrs.insert(key, yaml_scalar(value));
+            continue;
+        };
+
+        if key == "tags" {
+            let tags: Vec<String> =
+                items.iter().map(|t| t.replace(' ', "-")).collect();
+            attrs.insert(key, tags.join(" "));
+        } else if !items.is_empty() {
+            attrs.insert(key, items.join("\n"));
+        }
+    }
+
+    attrs
+}
+
+fn yaml_scalar(value: &str) -> String {
+    let value = value.trim();
+    if value.starts_with('"') {
+        if let Ok(s) = serde_json::from_str::<String>(value) {
+            return s;
+        }
+    }
+    if let Some(s) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\''))
+    {
+        return s.replace("''", "'");
+    }
+    // Strip comments.
+    match value.split_once(" #") {
+        Some((value, _)) => value.trim_end().to_owned(),
+        None => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_to_markdown() {
+        let outline: Outline = idm::from_str(
+            "\
+:title Notes
+:tags a b
+Topic *
+  :author Someone
+  Ont
+    :uri https://github.com/rsaarelm/ont
+    :tags rust
+  Details
+    Nested
+    Other
+      Deep
+  >-
+    #!/usr/bin/env python3
+    print(1 + 2)
+  ==
+    3",
+        )
+        .unwrap();
+
+        assert_eq!(
+            to_markdown(&outline, 2).unwrap(),
+            "\
+---
+title: Notes
+tags: [a, b]
+---
+
+# **Topic**
+
+author
+:   Someone
+
+- [Ont](https://github.com/rsaarelm/ont)
+  - *tags*: rust
+
+## Details
+
+- Nested
+- Other
+  - Deep
+
+```python3
+#!/usr/bin/env python3
+print(1 + 2)
+```
+
+```text
+3
+```
+"
+        );
+    }
+
+    #[test]
+    fn test_colon_lines() {
+        let outline = from_markdown("- :uri x\n  - :tags y\n\n:z\n");
+        let text = idm::to_string(&outline).unwrap();
+        assert_eq!(text, "\\:uri x\n  \\:tags y\n\n\\:z\n");
+        // Reading the text back gives no attributes.
+        let reread: Outline = idm::from_str(&text).unwrap();
+        assert!(reread.attrs.is_empty());
+        assert!(reread.children[0].body.attrs.is_empty());
+    }
+
+    #[test]
+    fn test_from_markdown() {
+        let outline = from_markdown(
+            "\
+---
+title: Notes
+tags:
+  - a
+  - b c
+---
+
+# **Topic**
+
+author
+:   Someone
+
+Some text
+over two lines.
+
+- [Ont](https://github.com/rsaarelm/ont)
+  - *tags*: rust
+- Item
+  continued
+  1. Nested
+
+```python
+#!/usr/bin/env python3
+print(1 + 2)
+```
+
+```text
+3
+```
+",
+        );
+
+        assert_eq!(
+            idm::to_string(&outline).unwrap(),
+            "\
+:title Notes
+:tags a b-c
+Topic *
+  :author Someone
+  Some text
+  over two lines.
+
+  Ont
+    :uri https://github.com/rsaarelm/ont
+    :tags rust
+  Item continued
+    Nested
+
+  >-.python
+    #!/usr/bin/env python3
+    print(1 + 2)
+  ==
+    3
+"
+        );
+    }
+}
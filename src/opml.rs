@@ -0,0 +1,241 @@
+use std::fmt::Write;
+
+use anyhow::{bail, Result};
+use lazy_regex::regex;
+use ont::{parse, Outline, Section};
+use roxmltree::Node;
+
+use crate::IoPipe;
+
+pub fn export(io: IoPipe) -> Result<()> {
+    let outline = io.read_outline()?;
+    io.write_text(to_opml(&outline)?)
+}
+
+pub fn import(io: IoPipe) -> Result<()> {
+    let outline = from_opml(&io.read_text()?)?;
+
+    // Input is XML, there's no outline layout to preserve.
+    io.write_imported(&outline)
+}
+
+/// Render an outline as an OPML document.
+///
+/// Toplevel attributes go in the OPML head and sections become `outline`
+/// elements with the headline in the `text` attribute. The `uri` attribute
+/// is written as the `xmlUrl` of a feed for sections with `:type rss` and as
+/// the `url` of a link otherwise.
+pub fn to_opml(outline: &Outline) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(out, r#"<opml version="2.0">"#)?;
+
+    writeln!(out, "  <head>")?;
+    for (k, v) in &outline.attrs {
+        let Some(name) = xml_name(k) else {
+            log::warn!("export_opml: Skipping attribute {k:?}");
+            continue;
+        };
+        writeln!(out, "    <{name}>{}</{name}>", escape(v))?;
+    }
+    writeln!(out, "  </head>")?;
+
+    writeln!(out, "  <body>")?;
+    write_outlines(&mut out, &outline.children, 2)?;
+    writeln!(out, "  </body>")?;
+    writeln!(out, "</opml>")?;
+    Ok(out)
+}
+
+fn write_outlines(
+    out: &mut String,
+    sections: &[Section],
+    depth: usize,
+) -> Result<()> {
+    let indent = "  ".repeat(depth);
+    for s in sections {
+        if s.head.trim().is_empty() && s.body.is_empty() {
+            continue;
+        }
+
+        write!(out, r#"{indent}<outline text="{}""#, escape(&s.head))?;
+        let attrs = &s.body.attrs;
+        if let Some(uri) = attrs.get("uri") {
+            if attrs.get("type").is_some_and(|t| t == "rss") {
+                write!(out, r#" xmlUrl="{}""#, escape(uri))?;
+            } else {
+                if !attrs.contains_key("type") {
+                    write!(out, r#" type="link""#)?;
+                }
+                write!(out, r#" url="{}""#, escape(uri))?;
+            }
+        }
+        for (k, v) in attrs.iter().filter(|(k, _)| *k != "uri") {
+            let Some(name) = xml_name(k) else {
+                log::warn!("export_opml: Skipping attribute {k:?}");
+                continue;
+            };
+            write!(out, r#" {name}="{}""#, escape(v))?;
+        }
+
+        if s.body.children.is_empty() {
+            writeln!(out, "/>")?;
+        } else {
+            writeln!(out, ">")?;
+            write_outlines(out, &s.body.children, depth + 1)?;
+            writeln!(out, "{indent}</outline>")?;
+        }
+    }
+    Ok(())
+}
+
+/// Parse an OPML document into an outline.
+pub fn from_opml(text: &str) -> Result<Outline> {
+    let doc = roxmltree::Document::parse(text)?;
+    let root = doc.root_element();
+    if !root.has_tag_name("opml") {
+        bail!("Not an OPML document");
+    }
+
+    let mut ret = Outline::default();
+    for node in root.children().filter(Node::is_element) {
+        if node.has_tag_name("head") {
+            for field in node.children().filter(Node::is_element) {
+                let value = field.text().unwrap_or("").trim();
+                if !value.is_empty() {
+                    ret.attrs.insert(
+                        parse::camel_to_kebab(field.tag_name().name()),
+                        value.into(),
+                    );
+                }
+            }
+        } else if node.has_tag_name("body") {
+            ret.children = outlines(node);
+        }
+    }
+
+    Ok(ret)
+}
+
+fn outlines(node: Node) -> Vec<Section> {
+    node.children()
+        .filter(|n| n.has_tag_name("outline"))
+        .map(|n| {
+            // Feed readers may only have a title.
+            let head =
+                n.attribute("text").or(n.attribute("title")).unwrap_or("");
+            let head = head.lines().collect::<Vec<_>>().join(" ");
+
+            // The URL that becomes the bookmark uri, feeds are more specific
+            // than links.
+            let uri_attr = ["xmlUrl", "url", "htmlUrl"]
+                .into_iter()
+                .find(|a| n.attribute(*a).is_some());
+
+            let mut body = Outline::default();
+            for a in n.attributes() {
+                let name = a.name();
+                if name == "text" || (name == "title" && a.value() == head) {
+                    continue;
+                }
+                if Some(name) == uri_attr {
+                    body.attrs.insert("uri".into(), a.value().into());
+                } else if !(name == "type"
+                    && a.value() == "link"
+                    && uri_attr == Some("url"))
+                {
+                    body.attrs
+                        .insert(parse::camel_to_kebab(name), a.value().into());
+                }
+            }
+            body.children = outlines(n);
+
+            Section::new(head, body)
+        })
+        .collect()
+}
+
+/// Convert a kebab-case attribute name to a camelCase XML name.
+fn xml_name(key: &str) -> Option<String> {
+    if !regex!(r"^[A-Za-z_][A-Za-z0-9_-]*$").is_match(key) {
+        return None;
+    }
+
+    let mut ret = String::new();
+    for (i, word) in key.split('-').enumerate() {
+        let mut chars = word.chars();
+        if i > 0 {
+            if let Some(c) = chars.next() {
+                ret.extend(c.to_uppercase());
+            }
+        }
+        ret.extend(chars);
+    }
+    Some(ret)
+}
+
+/// Escape text for XML attribute values and element contents.
+fn escape(s: &str) -> String {
+    let mut ret = String::new();
+    for c in s.chars() {
+        match c {
+            '&' => ret.push_str("&amp;"),
+            '<' => ret.push_str("&lt;"),
+            '>' => ret.push_str("&gt;"),
+            '"' => ret.push_str("&quot;"),
+            // Attribute values would have their newlines normalized away.
+            '\n' => ret.push_str("&#10;"),
+            c => ret.push(c),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_roundtrip() {
+        let opml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<opml version="2.0">
+  <head>
+    <title>Feeds &amp; links</title>
+    <dateCreated>Mon, 01 Jan 2024 00:00:00 GMT</dateCreated>
+  </head>
+  <body>
+    <outline text="Rust">
+      <outline text="This Week in Rust" xmlUrl="https://this-week-in-rust.org/rss.xml" type="rss" htmlUrl="https://this-week-in-rust.org/"/>
+    </outline>
+    <outline text="Ont" type="link" url="https://github.com/rsaarelm/ont" tags="rust idm"/>
+    <outline text="Plain note"/>
+  </body>
+</opml>
+"#;
+
+        let outline = from_opml(opml).unwrap();
+        assert_eq!(
+            idm::to_string(&outline).unwrap(),
+            "\
+:title Feeds & links
+:date-created Mon, 01 Jan 2024 00:00:00 GMT
+Rust
+  This Week in Rust
+    :uri https://this-week-in-rust.org/rss.xml
+    :type rss
+    :html-url https://this-week-in-rust.org/
+Ont
+  :uri https://github.com/rsaarelm/ont
+  :tags rust idm
+Plain note
+"
+        );
+
+        let roundtrip = from_opml(&to_opml(&outline).unwrap()).unwrap();
+        assert_eq!(
+            idm::to_string(&roundtrip).unwrap(),
+            idm::to_string(&outline).unwrap()
+        );
+    }
+}
@@ -0,0 +1,527 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use lazy_regex::regex;
+use ont::{parse, Outline, Section};
+
+use crate::{
+    markdown::text_outline,
+    weave::{
+        anonymous_script, is_anonymous, script_language, weave_filename,
+        OUTPUT_MARKER,
+    },
+    IoPipe,
+};
+
+pub fn export(io: IoPipe) -> Result<()> {
+    let outline = io.read_outline()?;
+    io.write_text(to_org(&outline)?)
+}
+
+pub fn import(io: IoPipe) -> Result<()> {
+    let outline = from_org(&io.read_text()?);
+
+    // Input is Org, there's no outline layout to preserve.
+    io.write_imported(&outline)
+}
+
+/// Render an outline as an Org document.
+///
+/// Sections become headlines and attributes go in property drawers, except
+/// for tags that use Org's tag syntax. Lines before the first headline of a
+/// block are written as plain text.
+pub fn to_org(outline: &Outline) -> Result<String> {
+    let mut out = String::new();
+
+    // File-level attributes.
+    let (keywords, props): (Vec<_>, Vec<_>) =
+        outline.attrs.iter().partition(|(k, v)| is_keyword(k, v));
+    if !props.is_empty() {
+        write_drawer(&mut out, props.into_iter())?;
+    }
+    for (k, v) in keywords {
+        if k == "tags" {
+            writeln!(out, "#+filetags: {}", org_tags(v).unwrap_or_default())?;
+        } else {
+            writeln!(out, "#+{k}: {v}")?;
+        }
+    }
+    if !out.is_empty() {
+        writeln!(out)?;
+    }
+
+    block(&mut out, &outline.children, 1)?;
+
+    let mut ret = out.trim_end().to_owned();
+    ret.push('\n');
+    Ok(ret)
+}
+
+/// Attributes that are written as `#+keyword` lines at the top of the file,
+/// the rest go in a file-level property drawer.
+fn is_keyword(key: &str, value: &str) -> bool {
+    if key == "tags" {
+        org_tags(value).is_some()
+    } else {
+        !value.contains('\n')
+    }
+}
+
+fn block(out: &mut String, sections: &[Section], depth: usize) -> Result<()> {
+    let mut headlines = false;
+
+    let mut i = 0;
+    while i < sections.len() {
+        let s = &sections[i];
+        i += 1;
+        let head = s.head.trim();
+
+        if let Some(name) = weave_filename(head) {
+            let lang = script_language(name, &s.body);
+            let mut args = lang.clone();
+            if !is_anonymous(name) {
+                write!(args, " :tangle {name}")?;
+            }
+            writeln!(out, "#+begin_src {}", args.trim())?;
+            for line in script_text(&s.body)?.lines() {
+                if line.starts_with(['*', ',']) || line.starts_with("#+") {
+                    // Escape lines that Org would read as markup.
+                    writeln!(out, ",{line}")?;
+                } else {
+                    writeln!(out, "{line}")?;
+                }
+            }
+            writeln!(out, "#+end_src")?;
+
+            if let Some(output) =
+                sections.get(i).filter(|o| o.head.trim() == OUTPUT_MARKER)
+            {
+                writeln!(out)?;
+                writeln!(out, "#+RESULTS:")?;
+                for line in script_text(&output.body)?.lines() {
+                    writeln!(out, ": {line}")?;
+                }
+                i += 1;
+            }
+            continue;
+        }
+
+        if !headlines && is_text(s) {
+            if let Some(comment) = head.strip_prefix("--") {
+                writeln!(out, "#{comment}")?;
+            } else {
+                let text = Outline::from_iter(std::iter::once(s.clone()));
+                write!(out, "{}", idm::to_string(&text)?)?;
+            }
+            continue;
+        }
+
+        // Blank lines between headlines have no place to go.
+        if head.is_empty() && s.body.is_empty() {
+            continue;
+        }
+
+        headlines = true;
+        write!(out, "{} ", "*".repeat(depth))?;
+        if let Some(head) = parse::important(head) {
+            write!(out, "[#A] {head}")?;
+        } else {
+            write!(out, "{head}")?;
+        }
+
+        let tags = s.body.attrs.get("tags").and_then(|t| org_tags(t));
+        if let Some(tags) = &tags {
+            write!(out, " {tags}")?;
+        }
+        writeln!(out)?;
+
+        let props = s
+            .body
+            .attrs
+            .iter()
+            .filter(|(k, _)| !(*k == "tags" && tags.is_some()));
+        if props.clone().next().is_some() {
+            write_drawer(out, props)?;
+        }
+
+        block(out, &s.body.children, depth + 1)?;
+    }
+
+    Ok(())
+}
+
+/// Text lines of a section body as a weave script would see them.
+fn script_text(body: &Outline) -> Result<String> {
+    Ok(idm::to_string(&Outline::from_iter(body.children.clone()))?)
+}
+
+/// Whether a section can be written as plain text instead of a headline.
+///
+/// Lines are text, and so are plain lists with nested items.
+fn is_text(s: &Section) -> bool {
+    let head = s.head.trim();
+    if head.starts_with(['*', '#', ':']) || !s.body.attrs.is_empty() {
+        // Org would read these as markup.
+        return false;
+    }
+    s.body.children.is_empty()
+        || (regex!(r"^([-+]|\d+[.)])\s").is_match(head)
+            && s.body.children.iter().all(is_text))
+}
+
+fn write_drawer<'a>(
+    out: &mut String,
+    attrs: impl Iterator<Item = (&'a String, &'a String)>,
+) -> Result<()> {
+    writeln!(out, ":PROPERTIES:")?;
+    for (k, v) in attrs {
+        // Multi-line values are continued with `+` properties.
+        for (i, line) in v.lines().enumerate() {
+            let plus = if i == 0 { "" } else { "+" };
+            writeln!(out, ":{k}{plus}: {line}")?;
+        }
+    }
+    writeln!(out, ":END:")?;
+    Ok(())
+}
+
+/// Convert an IDM tag list to Org's `:tag:` syntax.
+///
+/// Org tags can't contain dashes, so kebab-case tags are written with
+/// underscores. Returns `None` if the tags can't be written as Org tags.
+fn org_tags(tags: &str) -> Option<String> {
+    let tags: Vec<String> = tags
+        .split_whitespace()
+        .map(|t| t.replace('-', "_"))
+        .collect();
+    if tags.is_empty()
+        || !tags.iter().all(|t| regex!(r"^[\w@#%]+$").is_match(t))
+    {
+        return None;
+    }
+    Some(format!(":{}:", tags.join(":")))
+}
+
+/// Convert Org's `:tag:` syntax to an IDM tag list.
+fn idm_tags(tags: &str) -> String {
+    tags.split(':')
+        .filter(|t| !t.is_empty())
+        .map(|t| t.replace('_', "-"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse an Org document into an outline.
+///
+/// This reads back what `to_org` writes. Headlines become sections, property
+/// drawers and tags become attributes and source blocks become weave scripts.
+/// The text under headlines is kept as lines of text.
+pub fn from_org(text: &str) -> Outline {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let mut importer = Importer {
+        stack: vec![(0, Section::default())],
+        text: Vec::new(),
+    };
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        let trimmed = line.trim();
+
+        if let Some(caps) = regex!(r"^(\*+)\s+(.*?)\s*$").captures(line) {
+            importer.headline(caps[1].len(), &caps[2]);
+            continue;
+        }
+
+        if importer.is_preamble() {
+            // Property drawers right after the headline or at the start of
+            // the file.
+            if trimmed.eq_ignore_ascii_case(":PROPERTIES:") {
+                while i < lines.len() {
+                    let line = lines[i].trim();
+                    i += 1;
+                    if line.eq_ignore_ascii_case(":END:") {
+                        break;
+                    }
+                    if let Some(caps) =
+                        regex!(r"^:([^:\s]+):\s*(.*)$").captures(line)
+                    {
+                        importer.property(&caps[1], &caps[2]);
+                    }
+                }
+                continue;
+            }
+
+            // File keywords.
+            if importer.stack.len() == 1 {
+                if let Some(caps) =
+                    regex!(r"^#\+([A-Za-z_-]+):\s*(.*)$").captures(trimmed)
+                {
+                    let key = caps[1].to_lowercase();
+                    if !matches!(key.as_str(), "results" | "name") {
+                        let value = caps[2].trim();
+                        let attrs = &mut importer.top().body.attrs;
+                        if key == "filetags" {
+                            attrs.insert("tags".into(), idm_tags(value));
+                        } else {
+                            attrs.insert(key, value.into());
+                        }
+                        continue;
+                    }
+                }
+            }
+        }
+
+        if regex!(r"^:[A-Za-z_-]+:$").is_match(trimmed) {
+            // Other drawers would read as attributes in IDM, skip them.
+            log::info!("import_org: Skipping drawer {trimmed}");
+            while i < lines.len()
+                && !lines[i].trim().eq_ignore_ascii_case(":END:")
+            {
+                i += 1;
+            }
+            i += 1;
+            continue;
+        }
+
+        if let Some(caps) =
+            regex!(r"(?i)^#\+begin_src(\s+(.*))?$").captures(trimmed)
+        {
+            let args = caps.get(2).map_or("", |m| m.as_str());
+            let head = regex!(r":tangle\s+(\S+)")
+                .captures(args)
+                .map(|c| format!(">{}", &c[1]))
+                .filter(|head| weave_filename(head).is_some())
+                .unwrap_or_else(|| {
+                    // Keep the language of blocks that aren't files.
+                    anonymous_script(
+                        args.split_whitespace().next().unwrap_or(""),
+                    )
+                });
+
+            let mut code = Vec::new();
+            while i < lines.len() {
+                let line = lines[i];
+                i += 1;
+                if line.trim().eq_ignore_ascii_case("#+end_src") {
+                    break;
+                }
+                code.push(unescape(line));
+            }
+            importer.add(Section::new(head, text_outline(&code)));
+            continue;
+        }
+
+        if trimmed.eq_ignore_ascii_case("#+RESULTS:") {
+            let mut output = Vec::new();
+            if i < lines.len()
+                && lines[i].trim().eq_ignore_ascii_case("#+begin_example")
+            {
+                i += 1;
+                while i < lines.len() {
+                    let line = lines[i];
+                    i += 1;
+                    if line.trim().eq_ignore_ascii_case("#+end_example") {
+                        break;
+                    }
+                    output.push(unescape(line));
+                }
+            } else {
+                // Fixed-width lines.
+                while i < lines.len() {
+                    let line = lines[i].trim_start();
+                    if line == ":" {
+                        output.push("");
+                    } else if let Some(line) = line.strip_prefix(": ") {
+                        output.push(line);
+                    } else {
+                        break;
+                    }
+                    i += 1;
+                }
+            }
+            importer.add(Section::new(OUTPUT_MARKER, text_outline(&output)));
+            continue;
+        }
+
+        if let Some(comment) = trimmed.strip_prefix('#') {
+            if comment.is_empty() || comment.starts_with(' ') {
+                importer.text.push(format!("--{comment}"));
+                continue;
+            }
+        }
+
+        importer.text.push(line.to_owned());
+    }
+
+    importer.flush();
+    while importer.stack.len() > 1 {
+        importer.close();
+    }
+    importer.stack.pop().unwrap().1.body
+}
+
+struct Importer {
+    /// Headlines that can still get more content with their depth, innermost
+    /// last. The first one is the document root.
+    stack: Vec<(usize, Section)>,
+    /// Text lines that haven't been added to the innermost section yet.
+    text: Vec<String>,
+}
+
+impl Importer {
+    fn top(&mut self) -> &mut Section {
+        &mut self.stack.last_mut().unwrap().1
+    }
+
+    /// Whether we're at the start of a section, before any contents.
+    fn is_preamble(&self) -> bool {
+        self.text.iter().all(|a| a.trim().is_empty())
+            && self.stack.last().unwrap().1.body.children.is_empty()
+    }
+
+    fn close(&mut self) {
+        let (_, section) = self.stack.pop().unwrap();
+        self.top().body.push(section);
+    }
+
+    /// Add pending text lines to the innermost section.
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.text);
+
+        // Leading and trailing blank lines are just spacing.
+        let Some(start) = text.iter().position(|a| !a.trim().is_empty()) else {
+            return;
+        };
+        let end = text.iter().rposition(|a| !a.trim().is_empty()).unwrap();
+
+        let outline = text_outline(&text[start..=end]);
+        self.top().body.children.extend(outline.children);
+    }
+
+    fn add(&mut self, section: Section) {
+        self.flush();
+        self.top().body.push(section);
+    }
+
+    fn headline(&mut self, depth: usize, text: &str) {
+        self.flush();
+        while self.stack.len() > 1 && self.stack.last().unwrap().0 >= depth {
+            self.close();
+        }
+
+        let mut head = text;
+        let mut body = Outline::default();
+
+        if let Some(caps) = regex!(r"^(.*?)\s+(:[\w@#%:]+:)$").captures(head) {
+            body.attrs
+                .insert("tags".into(), idm_tags(caps.get(2).unwrap().as_str()));
+            head = caps.get(1).unwrap().as_str();
+        }
+
+        let head = match head.strip_prefix("[#A]") {
+            Some(head) => format!("{} *", head.trim()),
+            None => head.to_owned(),
+        };
+
+        self.stack.push((depth, Section::new(head, body)));
+    }
+
+    fn property(&mut self, key: &str, value: &str) {
+        let attrs = &mut self.top().body.attrs;
+        let value = value.trim();
+        if let Some(key) = key.strip_suffix('+') {
+            let old = attrs.entry(key.to_lowercase()).or_default();
+            if !old.is_empty() {
+                old.push('\n');
+            }
+            old.push_str(value);
+        } else {
+            attrs.insert(key.to_lowercase(), value.into());
+        }
+    }
+}
+
+/// Remove the comma Org uses to escape markup in blocks.
+fn unescape(line: &str) -> &str {
+    match line.strip_prefix(',') {
+        Some(rest)
+            if rest.starts_with(['*', ',']) || rest.starts_with("#+") =>
+        {
+            rest
+        }
+        _ => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_roundtrip() {
+        let org = "\
+#+title: Notes
+#+filetags: :a:b:
+
+Intro line
+# A comment
+* [#A] Topic :rust:async_io:
+:PROPERTIES:
+:author: Someone
+:END:
+Some text
+- list
+  - nested
+** Child
+:PROPERTIES:
+:uri: https://example.com
+:notes: First line
+:notes+: Second line
+:END:
+#+begin_src python3
+#!/usr/bin/env python3
+,* not a headline
+print(1 + 2)
+#+end_src
+
+#+RESULTS:
+: 3
+* Other
+";
+
+        let outline = from_org(org);
+        assert_eq!(
+            idm::to_string(&outline).unwrap(),
+            "\
+:title Notes
+:tags a b
+Intro line
+-- A comment
+Topic *
+  :tags rust async-io
+  :author Someone
+  Some text
+  - list
+    - nested
+  Child
+    :uri https://example.com
+    :notes
+      First line
+      Second line
+    >-.python3
+      #!/usr/bin/env python3
+      * not a headline
+      print(1 + 2)
+    ==
+      3
+Other
+"
+        );
+
+        assert_eq!(to_org(&outline).unwrap(), org);
+    }
+}
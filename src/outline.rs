@@ -34,6 +34,13 @@ impl Section {
         parse::wiki_word(head)
     }
 
+    /// Return whether the section is a weave script or script output whose
+    /// body is literal text instead of outline content.
+    pub fn is_verbatim(&self) -> bool {
+        parse::weave_filename(&self.head).is_some()
+            || self.head.trim() == parse::OUTPUT_MARKER
+    }
+
     pub fn tags(&self) -> Vec<String> {
         let mut tags = Vec::new();
 
@@ -42,9 +49,23 @@ impl Section {
             tags.push(parse::camel_to_kebab(t));
         }
 
-        // Tags from the :tags attribute.
-        if let Ok(Some(tags_attr)) = self.body.get::<Vec<String>>("tags") {
-            tags.extend(tags_attr);
+        tags.extend(self.explicit_tags());
+        tags
+    }
+
+    /// Tags written in the section, from the `:tags` attribute and inline
+    /// `#tags` in the headline.
+    pub fn explicit_tags(&self) -> Vec<String> {
+        let mut tags = self
+            .body
+            .get::<Vec<String>>("tags")
+            .unwrap_or_default()
+            .unwrap_or_default();
+
+        for t in parse::hashtags(&self.head) {
+            if !tags.iter().any(|a| a == t) {
+                tags.push(t.to_owned());
+            }
         }
 
         tags
@@ -113,6 +134,30 @@ impl Outline {
         self.context_iter(()).map(|x| x.1)
     }
 
+    /// Iterate recursive sections of the outline, skipping the literal text
+    /// in the bodies of weave scripts and their outputs.
+    pub fn content_iter(&self) -> impl Iterator<Item = &Section> {
+        self.context_iter(false).filter_map(|(in_verbatim, s)| {
+            if *in_verbatim {
+                return None;
+            }
+            *in_verbatim = s.is_verbatim();
+            Some(s)
+        })
+    }
+
+    /// Iterate mutable recursive sections of the outline, skipping the
+    /// literal text in the bodies of weave scripts and their outputs.
+    pub fn content_iter_mut(&mut self) -> impl Iterator<Item = &mut Section> {
+        self.context_iter_mut(false).filter_map(|(in_verbatim, s)| {
+            if *in_verbatim {
+                return None;
+            }
+            *in_verbatim = s.is_verbatim();
+            Some(s)
+        })
+    }
+
     /// Iterate recursive sections of the outline with a context object that
     /// is passed to child sections.
     pub fn context_iter<C: Clone>(&self, init: C) -> ContextIter<'_, C> {
@@ -179,6 +224,33 @@ impl Outline {
     pub fn push_line(&mut self, s: impl Into<String>) {
         self.children.push(Section::new(s, Default::default()));
     }
+
+    /// Merge another outline into this one.
+    ///
+    /// Sections with the same headline are merged recursively, other
+    /// sections are appended. Attributes already present in this outline
+    /// are kept.
+    pub fn merge(&mut self, other: Outline) {
+        for (k, v) in other.attrs {
+            self.attrs.entry(k).or_insert(v);
+        }
+
+        for s in other.children {
+            // Blank lines are layout, not something to merge.
+            if s.head.trim().is_empty() {
+                self.children.push(s);
+                continue;
+            }
+
+            if let Some(existing) =
+                self.children.iter_mut().find(|a| a.head == s.head)
+            {
+                existing.body.merge(s.body);
+            } else {
+                self.children.push(s);
+            }
+        }
+    }
 }
 
 impl From<((IndexMap<String, String>,), Vec<Section>)> for Outline {
@@ -409,3 +481,77 @@ impl fmt::Display for SimpleOutline {
         print(f, 0, self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let mut a: Outline = idm::from_str(
+            "\
+:title A
+Notes/
+  Foo
+    :tags x
+Bar",
+        )
+        .unwrap();
+        let b: Outline = idm::from_str(
+            "\
+:title B
+:author b
+Notes/
+  Foo
+    Child
+  Baz
+Qux",
+        )
+        .unwrap();
+
+        a.merge(b);
+
+        assert_eq!(
+            a.to_string(),
+            "\
+:title A
+:author b
+Notes/
+  Foo
+    :tags x
+    Child
+  Baz
+Bar
+Qux
+"
+        );
+    }
+
+    #[test]
+    fn test_inline_tags() {
+        let outline: Outline = idm::from_str(
+            "\
+Fix the parser #todo
+  :tags parser
+  Notes with an #idea
+  >hello.c
+    #include <stdio.h>
+    #define N 3
+  ==
+    #output",
+        )
+        .unwrap();
+
+        let tags: Vec<Vec<String>> =
+            outline.content_iter().map(|s| s.explicit_tags()).collect();
+        assert_eq!(
+            tags,
+            vec![
+                vec!["parser", "todo"],
+                vec!["idea"],
+                Vec::<&str>::new(),
+                Vec::new()
+            ]
+        );
+    }
+}
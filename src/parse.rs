@@ -1,5 +1,7 @@
 //! Parsing primitives
 
+use std::ops::Range;
+
 use lazy_regex::regex;
 use nom::{
     branch::alt, character::complete::one_of, combinator::recognize,
@@ -35,6 +37,127 @@ pub fn wiki_word(s: &str) -> Option<&str> {
     }
 }
 
+/// Find WikiWords mentioned in text.
+///
+/// Words are separated by whitespace and may have punctuation around them.
+/// URLs are skipped.
+pub fn wiki_words(s: &str) -> impl Iterator<Item = &str> {
+    s.split_whitespace()
+        .filter(|w| !w.contains("://"))
+        .filter_map(|w| {
+            wiki_word(w.trim_matches(|c: char| !c.is_alphanumeric()))
+        })
+}
+
+/// Character that starts an inline tag. It's fixed so that every tool reads
+/// the same words of a text as tags.
+const INLINE_TAG_PREFIX: char = '#';
+
+/// Rewrite inline `old` tags in text to `new`, return `None` if the text has
+/// no `old` tags.
+pub fn rename_inline_tag(s: &str, old: &str, new: &str) -> Option<String> {
+    replace_inline_tags(s, |t| (t == old).then(|| vec![new.to_owned()]))
+}
+
+/// Rewrite inline tags in text with the tags returned by `f`, return `None`
+/// if nothing was replaced.
+///
+/// When `f` returns an empty list the tag is removed from the text.
+pub fn replace_inline_tags(
+    s: &str,
+    mut f: impl FnMut(&str) -> Option<Vec<String>>,
+) -> Option<String> {
+    let mut ret = String::new();
+    let mut pos = 0;
+    let mut changed = false;
+    for r in inline_tag_spans(s) {
+        let Some(tags) = f(&s[r.clone()]) else {
+            continue;
+        };
+        changed = true;
+
+        // Include the prefix character in the replaced span.
+        let start = r.start - INLINE_TAG_PREFIX.len_utf8();
+        if tags.is_empty() {
+            // Remove the tag with the whitespace before it, or after it at
+            // the start of the text.
+            let trimmed = s[pos..start].trim_end();
+            if trimmed.is_empty() && s[r.end..].starts_with(' ') {
+                ret.push_str(trimmed);
+                pos = r.end + 1;
+            } else {
+                ret.push_str(trimmed);
+                pos = r.end;
+            }
+            continue;
+        }
+
+        ret.push_str(&s[pos..start]);
+        let tags: Vec<String> = tags
+            .into_iter()
+            .map(|t| format!("{INLINE_TAG_PREFIX}{t}"))
+            .collect();
+        ret.push_str(&tags.join(" "));
+        pos = r.end;
+    }
+    if !changed {
+        return None;
+    }
+    ret.push_str(&s[pos..]);
+    Some(ret)
+}
+
+/// Byte ranges of the inline tags in text, without the prefix characters.
+fn inline_tag_spans(s: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    s.char_indices()
+        .filter(move |&(i, c)| {
+            c == INLINE_TAG_PREFIX
+                && s[..i].chars().next_back().is_none_or(char::is_whitespace)
+        })
+        .filter_map(move |(i, c)| {
+            let start = i + c.len_utf8();
+            let tag = &s[start..];
+            let end = tag
+                .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '_'))
+                .unwrap_or(tag.len());
+            let tag = tag[..end].trim_end_matches(['-', '_']);
+            tag.starts_with(|c: char| c.is_alphabetic())
+                .then_some(start..start + tag.len())
+        })
+}
+
+/// Find inline tags like `#todo` in text.
+///
+/// The tag must start with a letter after the `#` and the `#` must be at the
+/// start of the text or after whitespace, so `C#` and `#1` aren't tags.
+pub fn hashtags(s: &str) -> impl Iterator<Item = &str> {
+    inline_tag_spans(s).map(move |r| &s[r])
+}
+
+/// Headline of the section under a weave script that holds its output.
+pub const OUTPUT_MARKER: &str = "==";
+
+/// Match weave script headlines like `>script.sh`, return the file name.
+pub fn weave_filename(head: &str) -> Option<&str> {
+    let head = head.trim().strip_prefix('>')?;
+
+    // XXX Filename validation could be more robust. Must be a
+    // valid filename that isn't trying to escape containment with
+    // ".." or by starting with '/', but it can include
+    // subdirectory structure.
+    if head.chars().any(|c| c.is_whitespace())
+        || head.contains("..")
+        || head.starts_with('/')
+    {
+        return None;
+    }
+    if !regex!(r"^[A-Za-z0-9_-][.A-Za-z0-9_/-]*$").is_match(head.as_ref()) {
+        return None;
+    }
+
+    Some(head)
+}
+
 /// Match important headlines that end with " *", return the part before the
 /// importance marker.
 pub fn important(s: &str) -> Option<&str> {
@@ -70,6 +193,18 @@ pub fn camel_to_kebab(input: &str) -> String {
     kebab
 }
 
+/// Convert a `kebab-case` string to `CamelCase`.
+pub fn kebab_to_camel(input: &str) -> String {
+    input
+        .split('-')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            let first = chars.next().into_iter().flat_map(char::to_uppercase);
+            first.chain(chars)
+        })
+        .collect()
+}
+
 /// Try to merge differntly formatted URLs pointing to the same thing into one identifier.
 pub fn normalized_url(url: &str) -> String {
     let url = url.trim();
@@ -145,6 +280,44 @@ mod tests {
         assert_eq!(wiki_word("Wiki"), None);
     }
 
+    #[test]
+    fn test_wiki_words() {
+        assert_eq!(
+            wiki_words("See WikiWord, (OtherPage) and https://x.com/FooBar")
+                .collect::<Vec<_>>(),
+            vec!["WikiWord", "OtherPage"]
+        );
+        assert_eq!(wiki_words("Nothing here").count(), 0);
+    }
+
+    #[test]
+    fn test_hashtags() {
+        assert_eq!(
+            hashtags("#todo Fix the C# parser, #web-dev, issue #12 #idea.")
+                .collect::<Vec<_>>(),
+            vec!["todo", "web-dev", "idea"]
+        );
+        assert_eq!(hashtags("# Heading and a/#fragment").count(), 0);
+        assert_eq!(hashtags("Call @home").count(), 0);
+        assert_eq!(
+            rename_inline_tag("#web and #webdev, #web.", "web", "www"),
+            Some("#www and #webdev, #www.".to_owned())
+        );
+        assert_eq!(rename_inline_tag("#webdev", "web", "www"), None);
+        assert_eq!(
+            replace_inline_tags("#todo Fix #later", |t| {
+                (t == "todo").then(Vec::new)
+            }),
+            Some("Fix #later".to_owned())
+        );
+        assert_eq!(
+            replace_inline_tags("Read #webdev book", |t| {
+                (t == "webdev").then(|| vec!["web".into(), "dev".into()])
+            }),
+            Some("Read #web #dev book".to_owned())
+        );
+    }
+
     #[test]
     fn test_important() {
         assert_eq!(important("Important *"), Some("Important"));
@@ -158,6 +331,10 @@ mod tests {
         assert_eq!(camel_to_kebab("Camel"), "camel");
         assert_eq!(camel_to_kebab("CamelCase666"), "camel-case-666");
         assert_eq!(camel_to_kebab("666Camel"), "666-camel");
+
+        for word in ["CamelCase", "Camel", "CamelCase666"] {
+            assert_eq!(kebab_to_camel(&camel_to_kebab(word)), word);
+        }
     }
 
     #[test]
@@ -47,7 +47,8 @@ pub fn import(io: IoPipe, include_excerpt: bool) -> Result<()> {
         .map(|(folder, o)| Section::new(folder, o))
         .collect();
 
-    io.write(&outline)
+    // Input is CSV, there's no outline layout to preserve.
+    io.write_imported(&outline)
 }
 
 pub fn export(io: IoPipe, folder: impl AsRef<str>) -> Result<()> {
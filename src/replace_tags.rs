@@ -1,72 +1,335 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::PathBuf,
+};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use indexmap::IndexMap;
+use lazy_regex::Regex;
 
-use crate::IoPipe;
+use ont::{parse, Outline, Section};
 
-pub fn rename(io: IoPipe, old: String, new: String) -> Result<()> {
+use crate::{tagged::TagExpr, wiki, IoPipe};
+
+pub fn rename(io: IoPipe, old: String, new: String, wiki: bool) -> Result<()> {
     let mut outline = io.read_outline()?;
 
-    let mut item_count = 0;
-    for s in outline.iter_mut() {
-        if let Some(mut tags) = s.body.get_mut::<Vec<String>>("tags").unwrap() {
-            for t in tags.iter_mut() {
-                if *t == old {
-                    *t = new.clone();
-                    item_count += 1;
-                }
-            }
+    // Tags of the outline itself are inherited by all the sections.
+    let mut item_count = rename_in(&mut outline, &old, &new)?;
+    for s in outline.content_iter_mut() {
+        item_count += rename_in(&mut s.body, &old, &new)?;
+        if let Some(head) = parse::rename_inline_tag(&s.head, &old, &new) {
+            s.head = head;
+            item_count += 1;
         }
     }
 
     eprintln!("Renamed {item_count} tags");
 
+    // Wiki pages are implicitly tagged with the kebab-case form of their
+    // title.
+    let page = outline
+        .iter()
+        .filter_map(|s| s.wiki_title())
+        .find(|t| parse::camel_to_kebab(t) == old)
+        .map(|t| t.to_owned());
+    if let Some(page) = page {
+        if wiki {
+            rename_page(&mut outline, &page, &new)?;
+        } else {
+            eprintln!(
+                "Wiki page {page} has the implicit tag {old}, \
+                 use --wiki to rename it"
+            );
+        }
+    }
+
     io.write(&outline)
 }
 
-pub fn replace(io: IoPipe, replacements: PathBuf) -> Result<()> {
+/// Rename a wiki page to the WikiWord form of its new implicit tag.
+fn rename_page(outline: &mut Outline, page: &str, tag: &str) -> Result<()> {
+    let title = parse::kebab_to_camel(tag);
+    if parse::wiki_word(&title).is_none() {
+        bail!("Can't rename wiki page {page}, {title} isn't a WikiWord");
+    }
+    let count = wiki::rename_page(outline, page, &title);
+    eprintln!("Renamed wiki page {page} to {title} in {count} places");
+    Ok(())
+}
+
+fn rename_in(body: &mut Outline, old: &str, new: &str) -> Result<usize> {
+    let mut item_count = 0;
+    if let Some(mut tags) = body.get_mut::<Vec<String>>("tags")? {
+        for t in tags.iter_mut() {
+            if *t == old {
+                *t = new.to_owned();
+                item_count += 1;
+            }
+        }
+    }
+    Ok(item_count)
+}
+
+/// Replacement written in place of the new tags to delete the old tag.
+const DELETE: &str = "-";
+
+pub fn replace(
+    io: IoPipe,
+    replacements: PathBuf,
+    scope: Option<&TagExpr>,
+    wiki: bool,
+) -> Result<()> {
     let mut outline = io.read_outline()?;
 
-    let replacements: HashMap<String, Vec<String>> =
-        idm::from_str(&std::fs::read_to_string(&replacements)?)?;
+    let rules = Rules::parse(&std::fs::read_to_string(&replacements)?)?;
+
+    // Scoping looks at the tags inherited from the parent sections.
+    let root_tags: BTreeSet<String> = outline
+        .get::<Vec<String>>("tags")?
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+
+    // Wiki pages in scope whose implicit tag has a rule.
+    let mut pages = Vec::new();
 
+    // Tags of the outline itself are inherited by all the sections.
     let mut item_count = 0;
-    for s in outline.iter_mut() {
-        if let Some(mut tags) = s.body.get_mut::<Vec<String>>("tags").unwrap() {
-            for i in (0..tags.len()).rev() {
-                if let Some(replacements) = replacements.get(&tags[i]) {
-                    // Empty items in replacement list are no-ops, you can't
-                    // remove tags without replacement with this.
-                    if replacements.is_empty() {
-                        continue;
-                    }
-
-                    item_count += 1;
-
-                    // Replace the original tag with the first replacement
-                    // one (unless the tag list already contains the
-                    // replacement).
-                    if !replacements.contains(&tags[i]) {
-                        if !tags.contains(&replacements[0]) {
-                            tags[i] = replacements[0].clone();
-                        } else {
-                            tags.swap_remove(i);
-                        }
-                    }
-
-                    // Append the remaining replacement tags unless they're
-                    // already found in the set.
-                    for t in replacements.iter().skip(1) {
-                        if !tags.contains(t) {
-                            tags.push(t.clone());
-                        }
-                    }
+    if scope.is_none_or(|e| e.matches(&root_tags)) {
+        item_count += replace_in(&rules, &mut outline)?;
+    }
+
+    for (ctx, s) in outline.context_iter_mut(Some(root_tags)) {
+        // Weave scripts and outputs are literal text.
+        let Some(inherited) = ctx.as_mut() else {
+            continue;
+        };
+        if s.is_verbatim() {
+            *ctx = None;
+            continue;
+        }
+
+        let mut set = inherited.clone();
+        set.extend(s.tags());
+        let in_scope = scope.is_none_or(|e| e.matches(&set));
+        inherited.extend(s.tags());
+
+        if in_scope {
+            item_count += replace_in(&rules, &mut s.body)?;
+            item_count += replace_inline(&rules, s);
+
+            if let Some(title) = s.wiki_title() {
+                let tag = parse::camel_to_kebab(title);
+                if let Some(new) = rules.get(&tag) {
+                    pages.push((title.to_owned(), tag, new));
                 }
             }
         }
     }
 
+    // Wiki pages are implicitly tagged with the kebab-case form of their
+    // title, a page can only be renamed to a single new tag.
+    for (page, tag, new) in pages {
+        match &new[..] {
+            [new] if wiki && new != DELETE && *new != tag => {
+                rename_page(&mut outline, &page, new)?;
+            }
+            _ if wiki => eprintln!(
+                "Can't rename wiki page {page}, the rule for {tag} doesn't \
+                 have a single new tag"
+            ),
+            _ => eprintln!(
+                "Wiki page {page} has the implicit tag {tag}, \
+                 use --wiki to rename it"
+            ),
+        }
+    }
+
     eprintln!("Replaced {item_count} tags");
 
     io.write(&outline)
 }
+
+/// Rewrite the `:tags` attribute of an outline, return the number of
+/// replaced tags.
+pub fn replace_in(rules: &Rules, body: &mut Outline) -> Result<usize> {
+    let Some(mut tags) = body.get_mut::<Vec<String>>("tags")? else {
+        return Ok(0);
+    };
+
+    let mut item_count = 0;
+    // Tags added after the tags that were there originally.
+    let mut added = Vec::new();
+    let mut new_tags = Vec::new();
+    // A replacement can be a tag the section already has, don't write it
+    // twice.
+    let push = |new_tags: &mut Vec<String>, tag: String| {
+        if !new_tags.contains(&tag) {
+            new_tags.push(tag);
+        }
+    };
+    for tag in tags.iter() {
+        let Some(replacements) = rules.get(tag) else {
+            push(&mut new_tags, tag.clone());
+            continue;
+        };
+        item_count += 1;
+
+        // Replace the original tag with the first replacement one and append
+        // the rest, unless the original tag is kept.
+        if replacements.contains(tag) {
+            push(&mut new_tags, tag.clone());
+            added.extend(replacements.into_iter().filter(|t| t != tag));
+        } else if replacements != [DELETE] {
+            let mut replacements = replacements.into_iter();
+            if let Some(first) = replacements.next() {
+                push(&mut new_tags, first);
+            }
+            added.extend(replacements);
+        }
+    }
+
+    for t in added {
+        push(&mut new_tags, t);
+    }
+    *tags = new_tags;
+    if tags.is_empty() {
+        drop(tags);
+        body.attrs.shift_remove("tags");
+    }
+
+    Ok(item_count)
+}
+
+/// Rewrite the inline `#tags` in the headline of a section.
+fn replace_inline(rules: &Rules, s: &mut Section) -> usize {
+    // Don't write tags that are already in the headline again.
+    let mut seen: BTreeSet<String> =
+        parse::hashtags(&s.head).map(|t| t.to_owned()).collect();
+
+    let mut item_count = 0;
+    let head = parse::replace_inline_tags(&s.head, |tag| {
+        let replacements = rules.get(tag)?;
+        item_count += 1;
+        if replacements == [DELETE] {
+            return Some(Vec::new());
+        }
+        Some(
+            replacements
+                .into_iter()
+                .filter(|t| t == tag || seen.insert(t.clone()))
+                .collect(),
+        )
+    });
+    if let Some(head) = head {
+        s.head = head;
+    }
+    item_count
+}
+
+/// Tag replacement rules.
+///
+/// Tags are looked up from literal tag rules first and then matched against
+/// regular expression rules written as `/regex/` in the order they appear
+/// in. Rules with no replacements do nothing.
+pub struct Rules {
+    tags: HashMap<String, Vec<String>>,
+    patterns: Vec<(Regex, Vec<String>)>,
+}
+
+impl Rules {
+    /// Parse rules from the text of a replacement file.
+    pub fn parse(text: &str) -> Result<Self> {
+        Self::new(idm::from_str(text)?)
+    }
+
+    fn new(rules: IndexMap<String, Vec<String>>) -> Result<Self> {
+        let mut tags = HashMap::new();
+        let mut patterns = Vec::new();
+        for (old, new) in rules {
+            // Empty items in replacement list are no-ops.
+            if new.is_empty() {
+                continue;
+            }
+            if new.len() > 1 && new.iter().any(|t| t == DELETE) {
+                bail!("Replacement of {old} mixes deletion and new tags");
+            }
+
+            match old.strip_prefix('/').and_then(|a| a.strip_suffix('/')) {
+                Some(re) => patterns.push((Regex::new(re)?, new)),
+                None => {
+                    tags.insert(old, new);
+                }
+            }
+        }
+        Ok(Rules { tags, patterns })
+    }
+
+    /// Return the replacements of a tag, with regex captures like `$1`
+    /// expanded.
+    fn get(&self, tag: &str) -> Option<Vec<String>> {
+        if let Some(new) = self.tags.get(tag) {
+            return Some(new.clone());
+        }
+
+        for (re, new) in &self.patterns {
+            if let Some(caps) = re.captures(tag) {
+                return Some(
+                    new.iter()
+                        .map(|t| {
+                            let mut ret = String::new();
+                            caps.expand(t, &mut ret);
+                            ret
+                        })
+                        .filter(|t| !t.is_empty())
+                        .collect(),
+                );
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rules() {
+        let rules = Rules::new(
+            idm::from_str(
+                "\
+colour color
+raindrop-import -
+/^imported-(.*)$/ $1
+ideas",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(rules.get("colour"), Some(vec!["color".to_owned()]));
+        assert_eq!(rules.get("raindrop-import"), Some(vec!["-".to_owned()]));
+        assert_eq!(rules.get("imported-rust"), Some(vec!["rust".to_owned()]));
+        assert_eq!(rules.get("ideas"), None);
+        assert_eq!(rules.get("rust"), None);
+
+        let mut s = Section::new(
+            "#raindrop-import #colour notes #color",
+            Outline::default(),
+        );
+        assert_eq!(replace_inline(&rules, &mut s), 2);
+        assert_eq!(s.head, "notes #color");
+
+        // Replacements that are already there aren't written twice.
+        let mut body: Outline =
+            idm::from_str(":tags colour color web").unwrap();
+        assert_eq!(replace_in(&rules, &mut body).unwrap(), 1);
+        assert_eq!(body.attrs["tags"], "color web");
+        let mut body: Outline = idm::from_str(":tags color colour").unwrap();
+        replace_in(&rules, &mut body).unwrap();
+        assert_eq!(body.attrs["tags"], "color");
+    }
+}
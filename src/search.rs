@@ -0,0 +1,349 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::Result;
+use base64::prelude::*;
+use lazy_regex::regex;
+use ont::{Outline, Section};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::IoPipe;
+
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 document length normalization.
+const B: f64 = 0.75;
+
+/// Weights of term occurrences in the searchable fields of a section.
+const HEAD_WEIGHT: f64 = 3.0;
+const TAGS_WEIGHT: f64 = 2.0;
+const ATTRS_WEIGHT: f64 = 1.0;
+const BODY_WEIGHT: f64 = 1.0;
+
+pub fn run(
+    io: IoPipe,
+    query: &[String],
+    limit: usize,
+    index_file: Option<&Path>,
+) -> Result<()> {
+    let outline = io.read_outline()?;
+    let sections: Vec<(Vec<String>, &Section)> = outline
+        .context_iter(Vec::new())
+        .map(|(path, s)| {
+            let ancestors = path.clone();
+            path.push(s.head.clone());
+            (ancestors, s)
+        })
+        .collect();
+
+    let index = match index_file {
+        Some(path) => Index::cached(path, &outline)?,
+        None => Index::new(&outline),
+    };
+
+    let mut ret = Outline::default();
+    for (i, _) in index.search(&Query::parse(query)).into_iter().take(limit) {
+        // Wrap the result in its ancestor headlines.
+        let (ancestors, s) = &sections[i];
+        let mut result = (*s).clone();
+        for head in ancestors.iter().rev() {
+            result = Section::new(head.clone(), Outline::from_iter([result]));
+        }
+        ret.push(result);
+    }
+
+    io.write_normalized(&ret)
+}
+
+/// Inverted index of the sections of an outline.
+///
+/// Sections are identified by their pre-order position in the outline.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    /// Hash of the indexed outline.
+    fingerprint: String,
+    /// Tokens of the fields of each section.
+    docs: Vec<Vec<Vec<String>>>,
+    /// Weighted lengths of the sections.
+    lens: Vec<f64>,
+    /// Number of indexed sections.
+    count: usize,
+    /// Sections and weighted term frequencies for each term.
+    postings: BTreeMap<String, BTreeMap<usize, f64>>,
+    /// Average weighted length of the indexed sections.
+    avg_len: f64,
+}
+
+impl Index {
+    pub fn new(outline: &Outline) -> Self {
+        let mut ret = Index {
+            fingerprint: fingerprint(outline),
+            ..Default::default()
+        };
+
+        let weights = [HEAD_WEIGHT, TAGS_WEIGHT, ATTRS_WEIGHT, BODY_WEIGHT];
+        for (has_parent, s) in outline.context_iter(false) {
+            let is_text_line = *has_parent && s.body.is_empty();
+            *has_parent = true;
+
+            // Text lines are indexed as the body of their parent section.
+            if is_text_line {
+                ret.docs.push(Vec::new());
+                ret.lens.push(0.0);
+                continue;
+            }
+
+            let fields = fields(s);
+            let i = ret.docs.len();
+            let mut len = 0.0;
+            for (field, weight) in fields.iter().zip(weights) {
+                for token in field {
+                    *ret.postings
+                        .entry(token.clone())
+                        .or_default()
+                        .entry(i)
+                        .or_default() += weight;
+                    len += weight;
+                }
+            }
+            ret.docs.push(fields);
+            ret.lens.push(len);
+            ret.count += 1;
+        }
+        ret.avg_len = ret.lens.iter().sum::<f64>() / ret.count.max(1) as f64;
+
+        ret
+    }
+
+    /// Load a saved index for the outline or build and save a new one if the
+    /// outline has changed.
+    pub fn cached(path: &Path, outline: &Outline) -> Result<Self> {
+        if let Ok(text) = fs::read_to_string(path) {
+            match serde_json::from_str::<Index>(&text) {
+                Ok(index) if index.fingerprint == fingerprint(outline) => {
+                    return Ok(index)
+                }
+                Ok(_) => log::info!("search: Rebuilding outdated index"),
+                Err(e) => log::warn!("search: Bad index file: {e}"),
+            }
+        }
+
+        let index = Index::new(outline);
+        fs::write(path, serde_json::to_string(&index)?)?;
+        Ok(index)
+    }
+
+    /// Find the sections matching every term of the query, best matches
+    /// first.
+    pub fn search(&self, query: &Query) -> Vec<(usize, f64)> {
+        let mut scores: Option<BTreeMap<usize, f64>> = None;
+
+        for term in &query.terms {
+            let mut term_scores = BTreeMap::new();
+            match term {
+                Term::Word(word) => self.score(word, &mut term_scores),
+                Term::Prefix(prefix) => {
+                    for word in self
+                        .postings
+                        .range(prefix.clone()..)
+                        .map(|(w, _)| w)
+                        .take_while(|w| w.starts_with(prefix.as_str()))
+                    {
+                        self.score(word, &mut term_scores);
+                    }
+                }
+                Term::Phrase(words) => {
+                    for word in words {
+                        self.score(word, &mut term_scores);
+                    }
+                    term_scores.retain(|&i, _| {
+                        self.docs[i].iter().any(|f| {
+                            f.windows(words.len()).any(|w| w == &words[..])
+                        })
+                    });
+                }
+            }
+
+            // Sections must match every term.
+            scores = Some(match scores {
+                None => term_scores,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(i, a)| {
+                        term_scores.get(&i).map(|b| (i, a + b))
+                    })
+                    .collect(),
+            });
+        }
+
+        let mut ret: Vec<(usize, f64)> =
+            scores.unwrap_or_default().into_iter().collect();
+        ret.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ret
+    }
+
+    /// Add the BM25 scores of a word to the sections that contain it.
+    fn score(&self, word: &str, scores: &mut BTreeMap<usize, f64>) {
+        let Some(postings) = self.postings.get(word) else {
+            return;
+        };
+
+        let n = self.count as f64;
+        let df = postings.len() as f64;
+        let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+
+        for (&i, &tf) in postings {
+            let norm = 1.0 - B + B * self.lens[i] / self.avg_len;
+            *scores.entry(i).or_default() +=
+                idf * tf * (K1 + 1.0) / (tf + K1 * norm);
+        }
+    }
+}
+
+/// Search terms that must all match.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Term {
+    Word(String),
+    /// Any word starting with the prefix, written as `prefix*`.
+    Prefix(String),
+    /// Consecutive words, written in double quotes.
+    Phrase(Vec<String>),
+}
+
+impl Query {
+    /// Parse command line query arguments.
+    ///
+    /// Arguments with spaces in them are phrases, so both `'"foo bar"'` and
+    /// `'foo bar'` on the command line search for the phrase.
+    pub fn parse(args: &[String]) -> Self {
+        let mut terms = Vec::new();
+        for arg in args {
+            if arg.trim().contains(char::is_whitespace) && !arg.contains('"') {
+                terms.push(Term::Phrase(tokens(arg)));
+                continue;
+            }
+
+            for caps in regex!(r#""([^"]*)"?|([^\s"]+)"#).captures_iter(arg) {
+                if let Some(phrase) = caps.get(1) {
+                    terms.push(Term::Phrase(tokens(phrase.as_str())));
+                } else if let Some(prefix) = caps[2].strip_suffix('*') {
+                    terms.extend(
+                        tokens(prefix).into_iter().last().map(Term::Prefix),
+                    );
+                } else {
+                    // Words with punctuation like `foo-bar` are phrases.
+                    let words = tokens(&caps[2]);
+                    if words.len() > 1 {
+                        terms.push(Term::Phrase(words));
+                    } else {
+                        terms.extend(words.into_iter().map(Term::Word));
+                    }
+                }
+            }
+        }
+
+        // Punctuation-only terms would never match anything.
+        terms.retain(|t| !matches!(t, Term::Phrase(p) if p.is_empty()));
+        Query { terms }
+    }
+}
+
+/// Tokens of the headline, tags, other attributes and text line children
+/// of a section.
+fn fields(s: &Section) -> Vec<Vec<String>> {
+    let mut tags = Vec::new();
+    let mut attrs = Vec::new();
+    for (k, v) in &s.body.attrs {
+        if k == "tags" {
+            tags.extend(tokens(v));
+        } else {
+            attrs.extend(tokens(v));
+        }
+    }
+
+    let body = s
+        .body
+        .children
+        .iter()
+        .filter(|c| c.body.is_empty())
+        .flat_map(|c| tokens(&c.head))
+        .collect();
+
+    vec![tokens(&s.head), tags, attrs, body]
+}
+
+/// Split text into lowercase words.
+pub fn tokens(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+fn fingerprint(outline: &Outline) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(idm::to_string(outline).unwrap_or_default().as_bytes());
+    BASE64_URL_SAFE_NO_PAD.encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(args: &[&str]) -> Query {
+        Query::parse(&args.iter().map(|a| a.to_string()).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_query() {
+        assert_eq!(
+            query(&["rust", "tok*", "\"async runtime\"", "no-std"]).terms,
+            vec![
+                Term::Word("rust".into()),
+                Term::Prefix("tok".into()),
+                Term::Phrase(vec!["async".into(), "runtime".into()]),
+                Term::Phrase(vec!["no".into(), "std".into()]),
+            ]
+        );
+        assert_eq!(
+            query(&["Async Runtime"]).terms,
+            vec![Term::Phrase(vec!["async".into(), "runtime".into()])]
+        );
+    }
+
+    #[test]
+    fn test_search() {
+        let outline: Outline = idm::from_str(
+            "\
+Tokio
+  :tags rust async
+  An async runtime for Rust.
+Async notes
+  Runtime of async code in Rust is tokio.
+Cooking
+  :tags food
+  Rust on the pan is bad.",
+        )
+        .unwrap();
+        let index = Index::new(&outline);
+        let heads = |q: &[&str]| {
+            index
+                .search(&query(q))
+                .into_iter()
+                .map(|(i, _)| outline.iter().nth(i).unwrap().head.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // Headline matches rank first.
+        assert_eq!(heads(&["tokio"]), vec!["Tokio", "Async notes"]);
+        assert_eq!(heads(&["rust", "async"]), vec!["Tokio", "Async notes"]);
+        assert_eq!(heads(&["\"async runtime\""]), vec!["Tokio"]);
+        assert_eq!(heads(&["coo*"]), vec!["Cooking"]);
+        assert!(heads(&["python"]).is_empty());
+    }
+}
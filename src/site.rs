@@ -0,0 +1,554 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Result};
+use html_escape::{encode_double_quoted_attribute as attr, encode_text};
+use ont::{parse, Outline, Section};
+
+use crate::{weave::weave_filename, IoPipe};
+
+const STYLE: &str = "\
+body { display: flex; font-family: sans-serif; margin: 0; }
+nav { min-width: 14em; padding: 1em; background: #f4f4f4; }
+nav ul, main ul ul { padding-left: 1.2em; }
+nav ul { list-style: none; }
+nav .current { font-weight: bold; }
+main { padding: 1em 2em; max-width: 50em; }
+table.attrs { border-collapse: collapse; margin: 0.2em 0; font-size: 90%; }
+table.attrs th { text-align: left; padding-right: 1em; color: #666; }
+pre { background: #f4f4f4; padding: 0.5em; }";
+
+pub fn run(io: IoPipe) -> Result<()> {
+    if !io.is_dest_collection() {
+        bail!("site: Output must be a directory, end the path with '/'");
+    }
+
+    let outline = io.read_outline()?;
+
+    let mut pages = Vec::new();
+    if io.is_source_collection() {
+        collect_pages(&mut pages, Path::new(""), &outline);
+    } else {
+        pages.push(Page {
+            path: "page.html".into(),
+            title: outline.attrs.get("title").cloned().unwrap_or("Page".into()),
+            outline: &outline,
+        });
+    }
+
+    let site = Site::new(&outline, pages);
+    io.write_files(site.render()?)
+}
+
+/// Page generated from an outline file.
+struct Page<'a> {
+    /// Path of the HTML file relative to the site root.
+    path: PathBuf,
+    title: String,
+    outline: &'a Outline,
+}
+
+fn collect_pages<'a>(
+    pages: &mut Vec<Page<'a>>,
+    dir: &Path,
+    outline: &'a Outline,
+) {
+    for s in &outline.children {
+        if s.head.trim().is_empty() {
+            continue;
+        }
+
+        if let Some(name) = s.head.strip_suffix('/') {
+            collect_pages(pages, &dir.join(name), &s.body);
+        } else if !s.head.ends_with(':') {
+            pages.push(Page {
+                path: dir.join(format!("{}.html", s.head)),
+                title: s
+                    .body
+                    .attrs
+                    .get("title")
+                    .cloned()
+                    .unwrap_or(s.head.clone()),
+                outline: &s.body,
+            });
+        }
+    }
+}
+
+struct Site<'a> {
+    root: &'a Outline,
+    pages: Vec<Page<'a>>,
+    /// Anchors of sections on their pages.
+    anchors: HashMap<*const Section, String>,
+    /// Sections of wiki pages and the file page they're on.
+    wiki: BTreeMap<String, (&'a Section, usize)>,
+    /// Tagged sections and the page they're on.
+    tags: BTreeMap<String, Vec<(&'a Section, usize)>>,
+}
+
+impl<'a> Site<'a> {
+    fn new(root: &'a Outline, pages: Vec<Page<'a>>) -> Self {
+        let mut anchors = HashMap::new();
+        let mut wiki = BTreeMap::new();
+        let mut tags: BTreeMap<String, Vec<_>> = BTreeMap::new();
+
+        for (i, page) in pages.iter().enumerate() {
+            for (n, s) in page.outline.content_iter().enumerate() {
+                let anchor = match s.wiki_title() {
+                    Some(title) => {
+                        if wiki.contains_key(title) {
+                            log::warn!("site: Duplicate wiki page {title}");
+                        } else {
+                            wiki.insert(title.to_owned(), (s, i));
+                        }
+                        title.to_owned()
+                    }
+                    None => format!("s{n}"),
+                };
+                anchors.insert(s as *const Section, anchor);
+
+                for tag in s.tags() {
+                    tags.entry(tag).or_default().push((s, i));
+                }
+            }
+        }
+
+        Site {
+            root,
+            pages,
+            anchors,
+            wiki,
+            tags,
+        }
+    }
+
+    fn render(&self) -> Result<Vec<(PathBuf, String)>> {
+        let mut files = Vec::new();
+
+        for page in &self.pages {
+            let mut html = String::new();
+            Renderer::new(self, &page.path).block(&mut html, page.outline)?;
+            files.push((
+                page.path.clone(),
+                self.page(&page.path, &page.title, &html)?,
+            ));
+        }
+
+        for (title, (section, i)) in &self.wiki {
+            let path = wiki_path(title);
+            let r = Renderer::new(self, &path);
+            let mut html = String::new();
+            writeln!(
+                html,
+                "<p>From <a href=\"{}#{}\">{}</a></p>",
+                attr(&r.href(&self.pages[*i].path)),
+                attr(title),
+                encode_text(&self.pages[*i].title)
+            )?;
+            r.block(&mut html, &section.body)?;
+            files.push((path.clone(), self.page(&path, title, &html)?));
+        }
+
+        // Tag index and a page for each tag.
+        let path = PathBuf::from("tags/index.html");
+        let r = Renderer::new(self, &path);
+        let mut html = String::from("<ul>\n");
+        for (tag, sections) in &self.tags {
+            writeln!(
+                html,
+                "<li><a href=\"{}\">{}</a> ({})</li>",
+                attr(&r.href(&tag_path(tag))),
+                encode_text(tag),
+                sections.len()
+            )?;
+        }
+        html.push_str("</ul>\n");
+        files.push((path.clone(), self.page(&path, "Tags", &html)?));
+
+        for (tag, sections) in &self.tags {
+            let path = tag_path(tag);
+            let r = Renderer::new(self, &path);
+            let mut html = String::from("<ul>\n");
+            for (s, i) in sections {
+                writeln!(
+                    html,
+                    "<li>{} <small>({})</small></li>",
+                    r.section_link(s, *i),
+                    encode_text(&self.pages[*i].title)
+                )?;
+            }
+            html.push_str("</ul>\n");
+            files.push((path.clone(), self.page(&path, tag, &html)?));
+        }
+
+        // Front page, unless the collection has its own.
+        let path = PathBuf::from("index.html");
+        if !self.pages.iter().any(|p| p.path == path) {
+            let r = Renderer::new(self, &path);
+            let mut html = String::new();
+            r.attrs(&mut html, &self.root.attrs)?;
+            if !self.wiki.is_empty() {
+                html.push_str("<h2>Wiki pages</h2>\n<ul>\n");
+                for title in self.wiki.keys() {
+                    writeln!(
+                        html,
+                        "<li><a href=\"{}\">{}</a></li>",
+                        attr(&r.href(&wiki_path(title))),
+                        encode_text(title)
+                    )?;
+                }
+                html.push_str("</ul>\n");
+            }
+            let title =
+                self.root.attrs.get("title").map_or("Index", |a| a.as_str());
+            files.push((path.clone(), self.page(&path, title, &html)?));
+        }
+
+        Ok(files)
+    }
+
+    /// Wrap page contents in the site layout.
+    fn page(&self, path: &Path, title: &str, content: &str) -> Result<String> {
+        let r = Renderer::new(self, path);
+
+        let mut nav = String::new();
+        writeln!(
+            nav,
+            "<ul>\n<li><a href=\"{}\">Index</a></li>\n\
+            <li><a href=\"{}\">Tags</a></li>\n</ul>",
+            attr(&r.href(Path::new("index.html"))),
+            attr(&r.href(Path::new("tags/index.html")))
+        )?;
+
+        // File tree.
+        let mut dir = PathBuf::new();
+        nav.push_str("<ul>\n");
+        for page in &self.pages {
+            let parent = page.path.parent().unwrap_or(Path::new(""));
+            if parent != dir {
+                // Close directories we left and open the ones we entered.
+                let common = dir
+                    .components()
+                    .zip(parent.components())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                for _ in common..dir.components().count() {
+                    nav.push_str("</ul></li>\n");
+                }
+                for c in parent.components().skip(common) {
+                    writeln!(
+                        nav,
+                        "<li>{}/<ul>",
+                        encode_text(&c.as_os_str().to_string_lossy())
+                    )?;
+                }
+                dir = parent.to_owned();
+            }
+
+            let name = page.path.file_stem().unwrap_or_default();
+            let class = if page.path == path {
+                " class=\"current\""
+            } else {
+                ""
+            };
+            writeln!(
+                nav,
+                "<li{class}><a href=\"{}\">{}</a></li>",
+                attr(&r.href(&page.path)),
+                encode_text(&name.to_string_lossy())
+            )?;
+        }
+        for _ in dir.components() {
+            nav.push_str("</ul></li>\n");
+        }
+        nav.push_str("</ul>\n");
+
+        Ok(format!(
+            "\
+<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>
+{STYLE}
+</style>
+</head>
+<body>
+<nav>
+{nav}</nav>
+<main>
+<h1>{title}</h1>
+{content}</main>
+</body>
+</html>
+",
+            title = encode_text(title),
+        ))
+    }
+}
+
+/// Writes the HTML for a specific page.
+struct Renderer<'a> {
+    site: &'a Site<'a>,
+    /// Path of the page being rendered, for relative links.
+    path: &'a Path,
+}
+
+impl<'a> Renderer<'a> {
+    fn new(site: &'a Site<'a>, path: &'a Path) -> Self {
+        Renderer { site, path }
+    }
+
+    /// Relative link from the current page to a path in the site.
+    fn href(&self, target: &Path) -> String {
+        let depth = self.path.components().count().saturating_sub(1);
+        let mut ret = "../".repeat(depth);
+        ret.push_str(&target.to_string_lossy());
+        ret
+    }
+
+    fn section_link(&self, s: &Section, page: usize) -> String {
+        let href = match s.wiki_title() {
+            Some(title) => self.href(&wiki_path(title)),
+            None => format!(
+                "{}#{}",
+                self.href(&self.site.pages[page].path),
+                self.site.anchors[&(s as *const Section)]
+            ),
+        };
+        format!(
+            "<a href=\"{}\">{}</a>",
+            attr(&href),
+            encode_text(parse::important(&s.head).unwrap_or(&s.head))
+        )
+    }
+
+    fn block(&self, out: &mut String, outline: &Outline) -> Result<()> {
+        self.attrs(out, &outline.attrs)?;
+
+        let items: Vec<&Section> = outline
+            .children
+            .iter()
+            .filter(|s| {
+                let head = s.head.trim();
+                !(head.is_empty() || head == "--" || head.starts_with("-- "))
+            })
+            .collect();
+        if items.is_empty() {
+            return Ok(());
+        }
+
+        out.push_str("<ul>\n");
+        for s in items {
+            if weave_filename(&s.head).is_some() || s.head == "==" {
+                let text = idm::to_string(&Outline::from_iter(
+                    s.body.children.clone(),
+                ))?;
+                writeln!(
+                    out,
+                    "<li><pre><code>{}</code></pre></li>",
+                    encode_text(text.trim_end())
+                )?;
+                continue;
+            }
+
+            let anchor = &self.site.anchors[&(s as *const Section)];
+            write!(out, "<li id=\"{}\">{}", attr(anchor), self.title(s))?;
+            if !s.body.is_empty() {
+                out.push('\n');
+                self.block(out, &s.body)?;
+            }
+            out.push_str("</li>\n");
+        }
+        out.push_str("</ul>\n");
+        Ok(())
+    }
+
+    fn title(&self, s: &Section) -> String {
+        let head = parse::important(&s.head).unwrap_or(&s.head);
+
+        let mut ret = match (s.wiki_title(), s.body.attrs.get("uri")) {
+            (Some(title), _) => format!(
+                "<a href=\"{}\">{}</a>",
+                attr(&self.href(&wiki_path(title))),
+                encode_text(head)
+            ),
+            (None, Some(uri)) => {
+                format!("<a href=\"{}\">{}</a>", attr(uri), encode_text(head))
+            }
+            (None, None) => self.inline(head),
+        };
+
+        if s.is_important() {
+            ret = format!("<strong>{ret}</strong>");
+        }
+        ret
+    }
+
+    fn attrs(
+        &self,
+        out: &mut String,
+        attrs: &indexmap::IndexMap<String, String>,
+    ) -> Result<()> {
+        if attrs.is_empty() {
+            return Ok(());
+        }
+
+        out.push_str("<table class=\"attrs\">\n");
+        for (k, v) in attrs {
+            let value = match k.as_str() {
+                "uri" => {
+                    format!("<a href=\"{}\">{}</a>", attr(v), encode_text(v))
+                }
+                "tags" => v
+                    .split_whitespace()
+                    .map(|t| {
+                        format!(
+                            "<a href=\"{}\">{}</a>",
+                            attr(&self.href(&tag_path(t))),
+                            encode_text(t)
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                _ => v
+                    .lines()
+                    .map(|a| self.inline(a))
+                    .collect::<Vec<_>>()
+                    .join("<br>"),
+            };
+            writeln!(
+                out,
+                "<tr><th>{}</th><td>{value}</td></tr>",
+                encode_text(k)
+            )?;
+        }
+        out.push_str("</table>\n");
+        Ok(())
+    }
+
+    /// Text with links for URLs and WikiWords.
+    fn inline(&self, text: &str) -> String {
+        let mut ret = String::new();
+        for (i, token) in text.split(' ').enumerate() {
+            if i > 0 {
+                ret.push(' ');
+            }
+
+            if token.starts_with("https://") || token.starts_with("http://") {
+                ret.push_str(&format!(
+                    "<a href=\"{}\">{}</a>",
+                    attr(token),
+                    encode_text(token)
+                ));
+                continue;
+            }
+
+            // Look for a WikiWord inside punctuation.
+            let word = token.trim_matches(|c: char| !c.is_alphanumeric());
+            if let Some(title) = parse::wiki_word(word)
+                .filter(|w| self.site.wiki.contains_key(*w))
+            {
+                let start = token.find(word).unwrap();
+                ret.push_str(&format!(
+                    "{}<a href=\"{}\">{}</a>{}",
+                    encode_text(&token[..start]),
+                    attr(&self.href(&wiki_path(title))),
+                    encode_text(title),
+                    encode_text(&token[start + word.len()..])
+                ));
+                continue;
+            }
+
+            ret.push_str(&encode_text(token));
+        }
+        ret
+    }
+}
+
+fn wiki_path(title: &str) -> PathBuf {
+    PathBuf::from(format!("wiki/{title}.html"))
+}
+
+fn tag_path(tag: &str) -> PathBuf {
+    // Keep tag file names safe.
+    let name: String = tag
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    PathBuf::from(format!("tags/{name}.html"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_site() {
+        let outline: Outline = idm::from_str(
+            "\
+notes
+  OntTool
+    :tags rust
+    Outline utility, see ReadMe.
+  Link *
+    :uri https://example.com
+    :tags rust
+sub/
+  more
+    ReadMe
+      Hello from OntTool.",
+        )
+        .unwrap();
+
+        let mut pages = Vec::new();
+        collect_pages(&mut pages, Path::new(""), &outline);
+        let site = Site::new(&outline, pages);
+        let files: BTreeMap<PathBuf, String> =
+            site.render().unwrap().into_iter().collect();
+
+        assert_eq!(
+            files
+                .keys()
+                .map(|p| p.to_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                "index.html",
+                "notes.html",
+                "sub/more.html",
+                "tags/index.html",
+                "tags/ont-tool.html",
+                "tags/read-me.html",
+                "tags/rust.html",
+                "wiki/OntTool.html",
+                "wiki/ReadMe.html",
+            ]
+        );
+
+        let notes = &files[Path::new("notes.html")];
+        assert!(notes.contains(
+            "<li id=\"OntTool\"><a href=\"wiki/OntTool.html\">OntTool</a>"
+        ));
+        assert!(notes.contains("see <a href=\"wiki/ReadMe.html\">ReadMe</a>."));
+        assert!(notes.contains(
+            "<strong><a href=\"https://example.com\">Link</a></strong>"
+        ));
+
+        let readme = &files[Path::new("sub/more.html")];
+        assert!(readme.contains("<a href=\"../wiki/OntTool.html\">"));
+        assert!(readme.contains("<a href=\"../tags/index.html\">Tags</a>"));
+
+        let rust = &files[Path::new("tags/rust.html")];
+        assert!(rust.contains("<a href=\"../notes.html#s2\">Link</a>"));
+    }
+}
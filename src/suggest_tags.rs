@@ -0,0 +1,218 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::Result;
+use ont::{Outline, Section};
+
+use crate::{search, IoPipe};
+
+/// Attribute the suggestions are written in.
+const SUGGESTED_TAGS: &str = "suggested-tags";
+
+/// Tags with fewer examples than this aren't suggested.
+const MIN_EXAMPLES: usize = 2;
+
+pub fn run(io: IoPipe, max: usize, write: bool) -> Result<()> {
+    let mut outline = io.read_outline()?;
+    let model = Model::new(&outline);
+
+    if write {
+        for s in outline.iter_mut() {
+            if !is_untagged_entry(s) {
+                continue;
+            }
+            let tags = model.suggest(&features(s), max);
+            if tags.is_empty() {
+                s.body.attrs.shift_remove(SUGGESTED_TAGS);
+            } else {
+                s.body.set(SUGGESTED_TAGS, &tags)?;
+            }
+        }
+        return io.write(&outline);
+    }
+
+    // List suggestions for review with the paths of the sections.
+    let mut list = Outline::default();
+    for (path, s) in outline.context_iter(Vec::new()) {
+        path.push(s.head.strip_suffix('/').unwrap_or(&s.head).to_owned());
+        if !is_untagged_entry(s) {
+            continue;
+        }
+        let tags = model.suggest(&features(s), max);
+        if !tags.is_empty() {
+            let mut body = Outline::default();
+            body.set(SUGGESTED_TAGS, &tags)?;
+            list.push(Section::new(path.join("/"), body));
+        }
+    }
+    io.write_text(idm::to_string(&list)?)
+}
+
+/// Entries like bookmarks have attributes but no tags.
+fn is_untagged_entry(s: &Section) -> bool {
+    !s.body.attrs.is_empty() && !s.body.attrs.contains_key("tags")
+}
+
+/// Naive Bayes model of how likely each tag is given the words of a section.
+#[derive(Default)]
+struct Model {
+    /// Number of tagged sections.
+    docs: usize,
+    /// Number of tagged sections with each tag.
+    tag_docs: HashMap<String, usize>,
+    /// Feature counts over all tagged sections.
+    counts: HashMap<String, usize>,
+    /// Feature counts of the sections with each tag.
+    tag_counts: HashMap<String, HashMap<String, usize>>,
+    /// Total feature counts, overall and per tag.
+    total: usize,
+    tag_total: HashMap<String, usize>,
+}
+
+impl Model {
+    /// Train the model on the sections with a `:tags` attribute.
+    fn new(outline: &Outline) -> Self {
+        let mut ret = Model::default();
+        for s in outline.iter() {
+            let Ok(Some(tags)) = s.body.get::<Vec<String>>("tags") else {
+                continue;
+            };
+            let features = features(s);
+            ret.docs += 1;
+            ret.total += features.len();
+            for f in &features {
+                *ret.counts.entry(f.clone()).or_default() += 1;
+            }
+            for t in tags {
+                *ret.tag_docs.entry(t.clone()).or_default() += 1;
+                *ret.tag_total.entry(t.clone()).or_default() += features.len();
+                let counts = ret.tag_counts.entry(t).or_default();
+                for f in &features {
+                    *counts.entry(f.clone()).or_default() += 1;
+                }
+            }
+        }
+        ret
+    }
+
+    /// Return the tags more likely present than not, most likely first.
+    fn suggest(&self, features: &BTreeSet<String>, max: usize) -> Vec<String> {
+        let vocabulary = self.counts.len() as f64;
+
+        let mut ret: Vec<(f64, &str)> = Vec::new();
+        for (tag, &n) in &self.tag_docs {
+            if n < MIN_EXAMPLES {
+                continue;
+            }
+            let tag_counts = &self.tag_counts[tag];
+            let tag_total = self.tag_total[tag] as f64;
+            let other_total = (self.total as f64) - tag_total;
+
+            // Log odds of the section having the tag versus not having it.
+            let mut score =
+                ((n as f64 + 1.0) / ((self.docs - n) as f64 + 1.0)).ln();
+            for f in features {
+                let Some(&count) = self.counts.get(f) else {
+                    continue;
+                };
+                let in_tag = tag_counts.get(f).copied().unwrap_or(0);
+                let p_tag = (in_tag as f64 + 1.0) / (tag_total + vocabulary);
+                let p_other = ((count - in_tag) as f64 + 1.0)
+                    / (other_total + vocabulary);
+                score += (p_tag / p_other).ln();
+            }
+
+            if score > 0.0 {
+                ret.push((score, tag));
+            }
+        }
+
+        ret.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(b.1)));
+        ret.into_iter()
+            .take(max)
+            .map(|(_, t)| t.to_owned())
+            .collect()
+    }
+}
+
+/// Words of the headline, attribute values and body text and the domain of
+/// the `uri` of a section.
+fn features(s: &Section) -> BTreeSet<String> {
+    let mut ret = BTreeSet::new();
+    let mut add_words = |text: &str| {
+        ret.extend(
+            search::tokens(text)
+                .into_iter()
+                .filter(|w| w.chars().count() > 2),
+        )
+    };
+
+    add_words(&s.head);
+    for (k, v) in &s.body.attrs {
+        if !matches!(k.as_str(), "tags" | "uri" | "added" | SUGGESTED_TAGS) {
+            add_words(v);
+        }
+    }
+    for c in &s.body.children {
+        if c.body.is_empty() {
+            add_words(&c.head);
+        }
+    }
+
+    if let Some(uri) = s.body.attrs.get("uri") {
+        if let Some(domain) = domain(uri) {
+            ret.insert(format!("domain:{domain}"));
+        }
+    }
+
+    ret
+}
+
+/// Host name of an URL without a `www.` prefix.
+fn domain(uri: &str) -> Option<&str> {
+    let (_, rest) = uri.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    let host = host.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+    Some(host.strip_prefix("www.").unwrap_or(host)).filter(|h| !h.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest() {
+        let outline: Outline = idm::from_str(
+            "\
+Tokio
+  :uri https://tokio.rs/
+  :tags rust async
+Serde
+  :uri https://github.com/serde-rs/serde
+  :tags rust
+Rust book
+  :uri https://doc.rust-lang.org/book/
+  :tags rust
+Pasta recipe
+  :uri https://www.cooking.example/pasta
+  :tags food
+Pizza recipe
+  :uri https://cooking.example/pizza
+  :tags food
+Soup recipe
+  :uri https://cooking.example/soup",
+        )
+        .unwrap();
+
+        let model = Model::new(&outline);
+        let soup = outline.children.last().unwrap();
+        assert!(is_untagged_entry(soup));
+        assert_eq!(model.suggest(&features(soup), 3), vec!["food"]);
+
+        assert_eq!(
+            domain("https://www.cooking.example/pasta"),
+            Some("cooking.example")
+        );
+        assert_eq!(domain("not a url"), None);
+    }
+}
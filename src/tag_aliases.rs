@@ -0,0 +1,104 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{bail, Result};
+use ont::Outline;
+
+/// Root attribute that lists the aliases of canonical tags.
+const TAG_ALIASES_ATTR: &str = "tag-aliases";
+
+/// Alternative spellings of tags.
+///
+/// The registry is read from the `:tag-aliases` attribute of the outline
+/// root, a `:tag-aliases.idm` file in a collection directory. Each line has a
+/// canonical tag followed by its aliases, `javascript js`. That's the reverse
+/// of a `replace-tags` line, which starts with the tag to replace.
+#[derive(Clone, Debug, Default)]
+pub struct TagAliases {
+    canonical: HashMap<String, String>,
+}
+
+impl TagAliases {
+    pub fn from_outline(outline: &Outline) -> Result<Self> {
+        let mut ret = TagAliases::default();
+        let Some(aliases) = outline.attrs.get(TAG_ALIASES_ATTR) else {
+            return Ok(ret);
+        };
+
+        // Parse the lines by hand, a single line wouldn't deserialize as a
+        // map.
+        for line in aliases.lines() {
+            let mut words = line.split_whitespace();
+            let Some(tag) = words.next() else {
+                continue;
+            };
+            for alias in words {
+                if alias == tag {
+                    continue;
+                }
+                if let Some(other) = ret.canonical.get(alias) {
+                    bail!("Tag alias {alias} is listed for {other} and {tag}");
+                }
+                ret.canonical.insert(alias.to_owned(), tag.to_owned());
+            }
+        }
+
+        // Don't allow chains of aliases.
+        for (alias, tag) in &ret.canonical {
+            if ret.canonical.contains_key(tag) {
+                bail!("Tag alias {alias} points to another alias {tag}");
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Return the canonical name of a tag.
+    pub fn canonical<'a>(&'a self, tag: &'a str) -> &'a str {
+        self.canonical.get(tag).map_or(tag, |t| t.as_str())
+    }
+
+    /// Return whether the tag is a non-canonical alias.
+    pub fn is_alias(&self, tag: &str) -> bool {
+        self.canonical.contains_key(tag)
+    }
+
+    /// Replace aliases in a set of tags with their canonical tags.
+    pub fn canonicalize(&self, tags: &mut BTreeSet<String>) {
+        if tags.iter().any(|t| self.is_alias(t)) {
+            *tags = tags.iter().map(|t| self.canonical(t).to_owned()).collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aliases() {
+        let outline: Outline = idm::from_str(
+            "\
+:tag-aliases
+  rust rustlang rust-lang
+  javascript js
+A
+  :tags rustlang web rust",
+        )
+        .unwrap();
+
+        let aliases = TagAliases::from_outline(&outline).unwrap();
+        assert_eq!(aliases.canonical("js"), "javascript");
+        assert_eq!(aliases.canonical("web"), "web");
+        assert!(aliases.is_alias("rust-lang"));
+
+        let mut tags: BTreeSet<String> =
+            outline.children[0].tags().into_iter().collect();
+        aliases.canonicalize(&mut tags);
+        assert_eq!(tags.into_iter().collect::<Vec<_>>(), vec!["rust", "web"]);
+
+        let outline: Outline =
+            idm::from_str(":tag-aliases javascript js").unwrap();
+        let aliases = TagAliases::from_outline(&outline).unwrap();
+        assert_eq!(aliases.canonical("js"), "javascript");
+    }
+}
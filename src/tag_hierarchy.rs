@@ -0,0 +1,128 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+use ont::Outline;
+
+/// Parent relations between tags.
+///
+/// Items tagged with a tag are implicitly tagged with all of its ancestors.
+#[derive(Clone, Debug, Default)]
+pub struct TagHierarchy {
+    parents: BTreeMap<String, String>,
+}
+
+impl TagHierarchy {
+    /// Load a hierarchy file.
+    ///
+    /// The file is an outline of tags where the children of a tag are its
+    /// subtags. Headlines can also be slash-separated paths like
+    /// `programming/rust`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let outline: Outline =
+            idm::from_str(&std::fs::read_to_string(path.as_ref())?)?;
+        Self::from_outline(&outline)
+    }
+
+    pub fn from_outline(outline: &Outline) -> Result<Self> {
+        let mut ret = TagHierarchy::default();
+        for (parent, s) in outline.context_iter(None::<String>) {
+            for tag in s.head.split('/').map(str::trim) {
+                if tag.is_empty() {
+                    continue;
+                }
+                if let Some(parent) = parent.replace(tag.to_owned()) {
+                    ret.insert(tag, &parent)?;
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    fn insert(&mut self, tag: &str, parent: &str) -> Result<()> {
+        if let Some(old) = self.parents.get(tag) {
+            if old != parent {
+                bail!("Tag {tag} has multiple parents, {old} and {parent}");
+            }
+            return Ok(());
+        }
+        if tag == parent || self.ancestors(parent).any(|a| a == tag) {
+            bail!("Tag hierarchy has a cycle at {tag}");
+        }
+        self.parents.insert(tag.to_owned(), parent.to_owned());
+        Ok(())
+    }
+
+    /// Iterate the parent, grandparent etc. of a tag.
+    pub fn ancestors<'a>(
+        &'a self,
+        tag: &'a str,
+    ) -> impl Iterator<Item = &'a str> + 'a {
+        std::iter::successors(self.parents.get(tag), |t| self.parents.get(*t))
+            .map(|t| t.as_str())
+    }
+
+    /// Return whether the tag appears in the hierarchy.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.parents.contains_key(tag)
+            || self.parents.values().any(|p| p == tag)
+    }
+
+    /// Slash-separated path from the root of the hierarchy to the tag.
+    pub fn path(&self, tag: &str) -> String {
+        let mut path: Vec<&str> = self.ancestors(tag).collect();
+        path.reverse();
+        path.push(tag);
+        path.join("/")
+    }
+
+    /// Add the ancestors of all the tags in the set to it.
+    pub fn expand(&self, tags: &mut BTreeSet<String>) {
+        let implied: Vec<String> = tags
+            .iter()
+            .flat_map(|t| self.ancestors(t))
+            .map(|t| t.to_owned())
+            .collect();
+        tags.extend(implied);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hierarchy() {
+        let outline: Outline = idm::from_str(
+            "\
+programming
+  rust
+    tokio
+  python
+hobbies/cooking",
+        )
+        .unwrap();
+        let hierarchy = TagHierarchy::from_outline(&outline).unwrap();
+
+        assert_eq!(
+            hierarchy.ancestors("tokio").collect::<Vec<_>>(),
+            vec!["rust", "programming"]
+        );
+        assert_eq!(hierarchy.path("cooking"), "hobbies/cooking");
+        assert!(hierarchy.contains("programming"));
+        assert!(!hierarchy.contains("music"));
+
+        let mut tags = BTreeSet::from(["tokio".to_owned(), "web".to_owned()]);
+        hierarchy.expand(&mut tags);
+        assert_eq!(
+            tags.into_iter().collect::<Vec<_>>(),
+            vec!["programming", "rust", "tokio", "web"]
+        );
+
+        let conflict: Outline =
+            idm::from_str("programming/rust\ngames/rust").unwrap();
+        assert!(TagHierarchy::from_outline(&conflict).is_err());
+    }
+}
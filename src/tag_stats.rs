@@ -0,0 +1,228 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+    iter::once,
+};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use ont::Outline;
+
+use crate::{tag_aliases::TagAliases, IoPipe};
+
+/// Tag statistics tables.
+#[derive(Copy, Clone, Debug, Default, ValueEnum)]
+pub enum Report {
+    /// How many items the most common tags share with each other.
+    #[default]
+    Cooccurrence,
+    /// Tags that are used only once and where.
+    Singletons,
+    /// Uses of the most common tags per year of the `:added` attribute.
+    Trend,
+    /// Uses of the most common tags in each toplevel section.
+    Subtrees,
+}
+
+/// Tagged section of an outline.
+struct Item {
+    /// Slash-separated headline path of the section.
+    path: String,
+    /// Index of the toplevel section the item is in.
+    subtree: usize,
+    added: Option<String>,
+    tags: BTreeSet<String>,
+}
+
+pub fn run(io: IoPipe, report: Report, top: usize) -> Result<()> {
+    let outline = io.read_outline()?;
+    let aliases = TagAliases::from_outline(&outline)?;
+
+    let items = items(&outline, &aliases);
+
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for item in &items {
+        for t in &item.tags {
+            *counts.entry(t).or_default() += 1;
+        }
+    }
+    let mut common: Vec<&str> = counts.keys().copied().collect();
+    common.sort_by_key(|t| (Reverse(counts[t]), *t));
+    common.truncate(top);
+
+    // Rows of whitespace-separated cells for `tf`, the last cell may have
+    // spaces in it.
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    match report {
+        Report::Cooccurrence => {
+            rows.push(
+                once("-")
+                    .chain(common.iter().copied())
+                    .map(String::from)
+                    .collect(),
+            );
+            for a in &common {
+                let mut row = vec![a.to_string()];
+                for b in &common {
+                    let n = items
+                        .iter()
+                        .filter(|i| i.tags.contains(*a) && i.tags.contains(*b))
+                        .count();
+                    row.push(n.to_string());
+                }
+                rows.push(row);
+            }
+        }
+        Report::Singletons => {
+            rows.push(vec!["tag".into(), "section".into()]);
+            let mut singletons: Vec<(&str, &str)> = items
+                .iter()
+                .flat_map(|i| i.tags.iter().map(|t| (t.as_str(), &*i.path)))
+                .filter(|(t, _)| counts[t] == 1)
+                .collect();
+            singletons.sort();
+            for (tag, path) in singletons {
+                rows.push(vec![tag.into(), path.into()]);
+            }
+        }
+        Report::Trend => {
+            // Items without a date are left out.
+            let years: BTreeSet<&str> = items
+                .iter()
+                .filter_map(|i| i.added.as_deref().and_then(year))
+                .collect();
+            rows.push(
+                once("-")
+                    .chain(years.iter().copied())
+                    .map(String::from)
+                    .collect(),
+            );
+            for tag in &common {
+                let mut row = vec![tag.to_string()];
+                for y in &years {
+                    let n = items
+                        .iter()
+                        .filter(|i| {
+                            i.tags.contains(*tag)
+                                && i.added.as_deref().and_then(year) == Some(y)
+                        })
+                        .count();
+                    row.push(n.to_string());
+                }
+                rows.push(row);
+            }
+        }
+        Report::Subtrees => {
+            rows.push(
+                once("section")
+                    .chain(common.iter().copied())
+                    .map(String::from)
+                    .collect(),
+            );
+            let mut subtrees: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+            for item in &items {
+                let row = subtrees
+                    .entry(item.subtree)
+                    .or_insert_with(|| vec![0; common.len()]);
+                for (i, tag) in common.iter().enumerate() {
+                    if item.tags.contains(*tag) {
+                        row[i] += 1;
+                    }
+                }
+            }
+            for (i, counts) in subtrees {
+                let row = once(headline_cell(&outline.children[i].head))
+                    .chain(counts.iter().map(|n| n.to_string()))
+                    .collect();
+                rows.push(row);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for row in rows {
+        writeln!(out, "{}", row.join(" "))?;
+    }
+    io.write_text(out)
+}
+
+/// Collect the sections with tags.
+fn items(outline: &Outline, aliases: &TagAliases) -> Vec<Item> {
+    let mut ret = Vec::new();
+    let mut toplevel_count = 0;
+    for (ctx, s) in outline.context_iter(Some(Vec::new())) {
+        // Paths are cleared under weave scripts and outputs, they're
+        // literal text.
+        let Some(path) = ctx.as_mut() else {
+            continue;
+        };
+        if path.is_empty() {
+            toplevel_count += 1;
+        }
+        if s.is_verbatim() {
+            *ctx = None;
+            continue;
+        }
+        path.push(s.head.strip_suffix('/').unwrap_or(&s.head).to_owned());
+
+        let mut tags: BTreeSet<String> =
+            s.explicit_tags().into_iter().collect();
+        if tags.is_empty() {
+            continue;
+        }
+        aliases.canonicalize(&mut tags);
+        ret.push(Item {
+            path: path.join("/"),
+            subtree: toplevel_count - 1,
+            added: s.body.attrs.get("added").cloned(),
+            tags,
+        });
+    }
+    ret
+}
+
+/// Headline as a single table cell, with the spaces written as underscores.
+fn headline_cell(head: &str) -> String {
+    let head = head.strip_suffix('/').unwrap_or(head);
+    head.split_whitespace().collect::<Vec<_>>().join("_")
+}
+
+/// Year of an ISO 8601 date.
+fn year(date: &str) -> Option<&str> {
+    let year = date.get(..4)?;
+    year.chars().all(|c| c.is_ascii_digit()).then_some(year)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items() {
+        let outline: Outline = idm::from_str(
+            "\
+:tags notes
+Rust
+  :tags rust
+  Tokio
+    :added 2024-01-01
+    :tags async
+Untagged
+  Nothing here
+Cooking
+  :tags food",
+        )
+        .unwrap();
+
+        let items = items(&outline, &TagAliases::default());
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[1].path, "Rust/Tokio");
+        assert_eq!(items[1].subtree, 0);
+        assert_eq!(items[2].subtree, 2);
+        assert_eq!(items[1].tags.iter().collect::<Vec<_>>(), vec!["async"]);
+        assert_eq!(items[1].added.as_deref().and_then(year), Some("2024"));
+        assert_eq!(headline_cell("Reading  list"), "Reading_list");
+        assert_eq!(headline_cell("notes/"), "notes");
+    }
+}
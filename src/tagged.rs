@@ -1,24 +1,49 @@
 use std::collections::BTreeSet;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use ont::{parse, Outline, Section};
 
-use crate::IoPipe;
+use crate::{tag_aliases::TagAliases, tag_hierarchy::TagHierarchy, IoPipe};
 
-pub fn run(io: IoPipe, tag_list: Vec<String>, flatten: bool) -> Result<()> {
+pub fn run(
+    io: IoPipe,
+    tag_list: Vec<String>,
+    flatten: bool,
+    hierarchy: &TagHierarchy,
+) -> Result<()> {
+    let mut expr = TagExpr::parse(&tag_list.join(" "))?;
     let outline = io.read_outline()?;
 
+    // Aliases are only resolved for matching, the output is written as is.
+    let aliases = TagAliases::from_outline(&outline)?;
+    expr.canonicalize(&aliases);
+
     if flatten {
         let mut sections = Vec::new();
-        collect_matching(&tag_list, BTreeSet::new(), &outline, &mut sections);
-        io.write(&Outline::from_iter(sections))
+        collect_matching(
+            &expr,
+            hierarchy,
+            &aliases,
+            BTreeSet::new(),
+            &outline,
+            &mut sections,
+        );
+        io.write_normalized(&Outline::from_iter(sections))
     } else {
-        io.write(&prune_outline(&tag_list, BTreeSet::new(), &outline))
+        io.write_normalized(&prune_outline(
+            &expr,
+            hierarchy,
+            &aliases,
+            BTreeSet::new(),
+            &outline,
+        ))
     }
 }
 
 fn prune_outline(
-    search_tags: &[String],
+    search_tags: &TagExpr,
+    hierarchy: &TagHierarchy,
+    aliases: &TagAliases,
     mut inherited_tags: BTreeSet<String>,
     outline: &Outline,
 ) -> Outline {
@@ -33,6 +58,11 @@ fn prune_outline(
     }
 
     for s in &outline.children {
+        // Weave scripts and outputs are literal text, not items.
+        if s.is_verbatim() {
+            continue;
+        }
+
         // Child is a match if it is a tag-bearing thing that matches all the search tags once we
         // include inherited tags.
         let is_match = {
@@ -41,7 +71,9 @@ fn prune_outline(
             if !tags.is_empty() {
                 let mut set = inherited_tags.clone();
                 set.extend(tags);
-                search_tags.iter().all(|t| set.contains(t))
+                aliases.canonicalize(&mut set);
+                hierarchy.expand(&mut set);
+                search_tags.matches(&set)
             } else {
                 false
             }
@@ -57,9 +89,15 @@ fn prune_outline(
         let body = if let Some(title) = s.wiki_title() {
             let mut set = inherited_tags.clone();
             set.insert(parse::camel_to_kebab(title));
-            prune_outline(search_tags, set, &s.body)
+            prune_outline(search_tags, hierarchy, aliases, set, &s.body)
         } else {
-            prune_outline(search_tags, inherited_tags.clone(), &s.body)
+            prune_outline(
+                search_tags,
+                hierarchy,
+                aliases,
+                inherited_tags.clone(),
+                &s.body,
+            )
         };
 
         // Keep the section if at least some children survived.
@@ -72,7 +110,9 @@ fn prune_outline(
 }
 
 fn collect_matching(
-    search_tags: &[String],
+    search_tags: &TagExpr,
+    hierarchy: &TagHierarchy,
+    aliases: &TagAliases,
     mut inherited_tags: BTreeSet<String>,
     outline: &Outline,
     out: &mut Vec<Section>,
@@ -82,13 +122,19 @@ fn collect_matching(
     }
 
     for s in &outline.children {
+        if s.is_verbatim() {
+            continue;
+        }
+
         let is_match = {
             let tags = s.tags();
 
             if !tags.is_empty() {
                 let mut set = inherited_tags.clone();
                 set.extend(tags);
-                search_tags.iter().all(|t| set.contains(t))
+                aliases.canonicalize(&mut set);
+                hierarchy.expand(&mut set);
+                search_tags.matches(&set)
             } else {
                 false
             }
@@ -101,9 +147,200 @@ fn collect_matching(
         if let Some(title) = s.wiki_title() {
             let mut set = inherited_tags.clone();
             set.insert(parse::camel_to_kebab(title));
-            collect_matching(search_tags, set, &s.body, out);
+            collect_matching(
+                search_tags,
+                hierarchy,
+                aliases,
+                set,
+                &s.body,
+                out,
+            );
         } else {
-            collect_matching(search_tags, inherited_tags.clone(), &s.body, out);
+            collect_matching(
+                search_tags,
+                hierarchy,
+                aliases,
+                inherited_tags.clone(),
+                &s.body,
+                out,
+            );
+        }
+    }
+}
+
+/// Boolean expression of tags.
+///
+/// Space-separated terms must all match, `|` separates alternatives, `!`
+/// negates a term and parentheses group terms, eg.
+/// `rust (async | tokio) !deprecated`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagExpr {
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Vec<TagExpr>),
+    Or(Vec<TagExpr>),
+}
+
+impl TagExpr {
+    pub fn parse(s: &str) -> Result<Self> {
+        let tokens: Vec<&str> = lazy_regex::regex!(r"[()|!]|[^\s()|!]+")
+            .find_iter(s)
+            .map(|m| m.as_str())
+            .collect();
+
+        let mut pos = 0;
+        let ret = Self::parse_or(&tokens, &mut pos)?;
+        if pos < tokens.len() {
+            bail!("Unexpected {:?} in tag expression", tokens[pos]);
+        }
+        Ok(ret)
+    }
+
+    fn parse_or(tokens: &[&str], pos: &mut usize) -> Result<Self> {
+        let mut terms = vec![Self::parse_and(tokens, pos)?];
+        while tokens.get(*pos) == Some(&"|") {
+            *pos += 1;
+            terms.push(Self::parse_and(tokens, pos)?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.pop().unwrap()
+        } else {
+            TagExpr::Or(terms)
+        })
+    }
+
+    fn parse_and(tokens: &[&str], pos: &mut usize) -> Result<Self> {
+        let mut terms = Vec::new();
+        while let Some(&token) = tokens.get(*pos) {
+            if token == "|" || token == ")" {
+                break;
+            }
+            terms.push(Self::parse_term(tokens, pos)?);
+        }
+        Ok(match terms.len() {
+            0 => bail!("Missing tag in tag expression"),
+            1 => terms.pop().unwrap(),
+            _ => TagExpr::And(terms),
+        })
+    }
+
+    fn parse_term(tokens: &[&str], pos: &mut usize) -> Result<Self> {
+        let Some(&token) = tokens.get(*pos) else {
+            bail!("Missing tag in tag expression");
+        };
+        *pos += 1;
+        match token {
+            "!" => Ok(TagExpr::Not(Box::new(Self::parse_term(tokens, pos)?))),
+            "(" => {
+                let ret = Self::parse_or(tokens, pos)?;
+                if tokens.get(*pos) != Some(&")") {
+                    bail!("Unclosed parenthesis in tag expression");
+                }
+                *pos += 1;
+                Ok(ret)
+            }
+            "|" | ")" => bail!("Unexpected {token:?} in tag expression"),
+            tag => Ok(TagExpr::Tag(tag.to_owned())),
+        }
+    }
+
+    /// Replace tag aliases in the expression with canonical tags.
+    pub fn canonicalize(&mut self, aliases: &TagAliases) {
+        match self {
+            TagExpr::Tag(t) => *t = aliases.canonical(t).to_owned(),
+            TagExpr::Not(e) => e.canonicalize(aliases),
+            TagExpr::And(es) | TagExpr::Or(es) => {
+                es.iter_mut().for_each(|e| e.canonicalize(aliases))
+            }
+        }
+    }
+
+    /// Return whether a set of tags satisfies the expression.
+    pub fn matches(&self, tags: &BTreeSet<String>) -> bool {
+        match self {
+            TagExpr::Tag(t) => tags.contains(t),
+            TagExpr::Not(e) => !e.matches(tags),
+            TagExpr::And(es) => es.iter().all(|e| e.matches(tags)),
+            TagExpr::Or(es) => es.iter().any(|e| e.matches(tags)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_expr() {
+        let expr = TagExpr::parse("rust (async | tokio) !deprecated").unwrap();
+        let tags = |s: &str| s.split(' ').map(|t| t.to_owned()).collect();
+
+        assert!(expr.matches(&tags("rust async")));
+        assert!(expr.matches(&tags("rust tokio networking")));
+        assert!(!expr.matches(&tags("rust")));
+        assert!(!expr.matches(&tags("rust tokio deprecated")));
+        assert!(!expr.matches(&tags("async tokio")));
+
+        assert!(TagExpr::parse("rust (async").is_err());
+        assert!(TagExpr::parse("rust | ").is_err());
+        assert!(TagExpr::parse("rust)").is_err());
+    }
+
+    #[test]
+    fn test_aliases() {
+        let outline: Outline = idm::from_str(
+            "\
+:tag-aliases javascript js
+A
+  :tags color js
+B
+  :tags color",
+        )
+        .unwrap();
+        let aliases = TagAliases::from_outline(&outline).unwrap();
+        let mut expr = TagExpr::parse("javascript").unwrap();
+        expr.canonicalize(&aliases);
+
+        // Matches with the alias are written unchanged.
+        let pruned = prune_outline(
+            &expr,
+            &TagHierarchy::default(),
+            &aliases,
+            BTreeSet::new(),
+            &outline,
+        );
+        assert_eq!(idm::to_string(&pruned).unwrap(), "A\n  :tags color js\n");
+    }
+
+    #[test]
+    fn test_comments_not_in_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("in.idm");
+        let output = dir.path().join("out.idm");
+        std::fs::write(
+            &input,
+            "\
+-- File header comment
+Rust
+  -- Section about rust
+  Tokio
+    :tags async",
+        )
+        .unwrap();
+
+        let io = IoPipe::try_from(crate::IoArgs {
+            input: vec![input],
+            merge: Default::default(),
+            at: None,
+            lines: None,
+            in_place: false,
+            output: Some(output.clone()),
+        })
+        .unwrap();
+        run(io, vec!["async".into()], false, &TagHierarchy::default()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(output).unwrap(),
+            "Rust\n  Tokio\n    :tags async\n"
+        );
+    }
+}
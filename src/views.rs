@@ -0,0 +1,365 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+use base64::prelude::*;
+use ont::{parse, Outline, Section};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    tag_aliases::TagAliases, tag_hierarchy::TagHierarchy, tagged::TagExpr,
+    IoPipe,
+};
+
+/// Attribute of the copies written into views that identifies the original
+/// section with a fingerprint of its contents and its headline path.
+const VIEW_SOURCE: &str = "view-source";
+
+/// Named tag queries.
+///
+/// The definition file has lines with a view name followed by a tag
+/// expression like the ones `tagged` takes, `rust-reading rust !read`.
+/// Lines starting with `--` are comments.
+pub struct Views(Vec<(String, TagExpr)>);
+
+impl Views {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::parse(&std::fs::read_to_string(path.as_ref())?)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut ret = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with("--") {
+                continue;
+            }
+            let Some((name, query)) = line.split_once(char::is_whitespace)
+            else {
+                bail!("View {line} has no tag query");
+            };
+            if ret.iter().any(|(n, _)| n == name) {
+                bail!("View {name} is defined twice");
+            }
+            ret.push((name.to_owned(), TagExpr::parse(query)?));
+        }
+        Ok(Views(ret))
+    }
+}
+
+/// Write the views as an outline with a section for each view, or a
+/// directory with a file for each view, that has copies of the matching
+/// sections.
+pub fn run(io: IoPipe, views: &Views, hierarchy: &TagHierarchy) -> Result<()> {
+    let outline = io.read_outline()?;
+    io.write_normalized(&materialize(&outline, views, hierarchy)?)
+}
+
+fn materialize(
+    outline: &Outline,
+    views: &Views,
+    hierarchy: &TagHierarchy,
+) -> Result<Outline> {
+    let aliases = TagAliases::from_outline(outline)?;
+    let paths = Paths::new(outline);
+
+    let mut ret = Outline::default();
+    for (name, expr) in &views.0 {
+        let mut expr = expr.clone();
+        expr.canonicalize(&aliases);
+
+        let mut matches = Vec::new();
+        collect(
+            &expr,
+            hierarchy,
+            &aliases,
+            BTreeSet::new(),
+            &mut Vec::new(),
+            outline,
+            &mut matches,
+        );
+
+        let mut body = Outline::default();
+        for (path, s) in matches {
+            if paths.is_ambiguous(&path) {
+                eprintln!(
+                    "Skipping {path} in view {name}, the path isn't unique"
+                );
+                continue;
+            }
+            body.push(tag_copy(&path, s)?);
+        }
+        ret.push(Section::new(name.clone(), body));
+    }
+    Ok(ret)
+}
+
+/// Write edits made to the sections in views back into the input outline.
+pub fn sync(io: IoPipe, views: impl AsRef<Path>) -> Result<()> {
+    let mut outline = io.read_outline()?;
+    let views: Outline = if views.as_ref().is_dir() {
+        ont::read_directory(views)?.0
+    } else {
+        idm::from_str(&std::fs::read_to_string(views.as_ref())?)?
+    };
+
+    let count = apply(&mut outline, &views)?;
+    eprintln!("Updated {count} sections");
+
+    io.write(&outline)
+}
+
+/// Replace the sections of the outline that were edited in the views, return
+/// the number of replaced sections.
+fn apply(outline: &mut Outline, views: &Outline) -> Result<usize> {
+    let paths = Paths::new(outline);
+
+    // Changed sections by their index path in the input.
+    let mut edits: BTreeMap<Vec<usize>, (&str, String, Section)> =
+        BTreeMap::new();
+    for view in &views.children {
+        let name = view.head.strip_suffix('/').unwrap_or(&view.head);
+        for copy in &view.body.children {
+            let mut copy = copy.clone();
+            let Some((hash, path)) =
+                copy.body.attrs.shift_remove(VIEW_SOURCE).and_then(|a| {
+                    a.split_once(' ').map(|(h, p)| (h.to_owned(), p.to_owned()))
+                })
+            else {
+                eprintln!(
+                    "Skipping {} in view {name}, it's not from the input",
+                    copy.head
+                );
+                continue;
+            };
+
+            let text = idm::to_string(&copy)?;
+            if fingerprint(&text) == hash {
+                // Not edited.
+                continue;
+            }
+
+            if paths.is_ambiguous(&path) {
+                bail!("Can't sync {path} from {name}, the path isn't unique");
+            }
+            let Some(idx) = paths.get(&path) else {
+                bail!("Can't sync {path} from {name}, it's not in the input");
+            };
+            if fingerprint(&idm::to_string(section(outline, idx))?) != hash {
+                bail!(
+                    "Can't sync {path} from {name}, it was changed in the \
+                     input after the view was written"
+                );
+            }
+
+            if let Some((other, other_text, _)) = edits.get(idx) {
+                if *other_text != text {
+                    bail!(
+                        "{path} was edited differently in {other} and {name}"
+                    );
+                }
+                continue;
+            }
+            edits.insert(idx.to_vec(), (name, text, copy));
+        }
+    }
+
+    // Replacing a section would undo the edits made to its subsections in
+    // other views.
+    for (a, (view_a, _, _)) in &edits {
+        for (b, (view_b, _, _)) in &edits {
+            if b.len() > a.len() && b.starts_with(a) {
+                bail!(
+                    "Both a section in {view_a} and its subsection in \
+                     {view_b} were edited"
+                );
+            }
+        }
+    }
+
+    let count = edits.len();
+    for (idx, (_, _, copy)) in edits {
+        *section_mut(outline, &idx) = copy;
+    }
+    Ok(count)
+}
+
+/// Copy a section for a view with the identity of the original.
+fn tag_copy(path: &str, s: &Section) -> Result<Section> {
+    let hash = fingerprint(&idm::to_string(s)?);
+    let mut copy = s.clone();
+    copy.body.attrs.shift_insert(
+        0,
+        VIEW_SOURCE.to_owned(),
+        format!("{hash} {path}"),
+    );
+    Ok(copy)
+}
+
+fn fingerprint(text: &str) -> String {
+    let hash = Sha256::digest(text.as_bytes());
+    BASE64_URL_SAFE_NO_PAD.encode(&hash[..8])
+}
+
+/// Collect the topmost sections that match the query with their paths.
+fn collect<'a>(
+    expr: &TagExpr,
+    hierarchy: &TagHierarchy,
+    aliases: &TagAliases,
+    mut inherited_tags: BTreeSet<String>,
+    path: &mut Vec<String>,
+    outline: &'a Outline,
+    out: &mut Vec<(String, &'a Section)>,
+) {
+    if let Ok(Some(tags)) = outline.get::<Vec<String>>("tags") {
+        inherited_tags.extend(tags);
+    }
+
+    for s in &outline.children {
+        // Weave scripts and outputs are literal text, not items.
+        if s.is_verbatim() {
+            continue;
+        }
+        path.push(head_name(s).to_owned());
+
+        let tags = s.tags();
+        let mut set: BTreeSet<String> =
+            inherited_tags.iter().chain(&tags).cloned().collect();
+        aliases.canonicalize(&mut set);
+        hierarchy.expand(&mut set);
+
+        if !tags.is_empty() && expr.matches(&set) {
+            out.push((path.join("/"), s));
+        } else {
+            let mut inherited = inherited_tags.clone();
+            if let Some(title) = s.wiki_title() {
+                inherited.insert(parse::camel_to_kebab(title));
+            }
+            collect(expr, hierarchy, aliases, inherited, path, &s.body, out);
+        }
+
+        path.pop();
+    }
+}
+
+fn head_name(s: &Section) -> &str {
+    s.head.strip_suffix('/').unwrap_or(&s.head)
+}
+
+/// Slash-separated headline paths of all the sections of an outline, with
+/// the index paths of the sections they refer to.
+struct Paths(BTreeMap<String, Vec<Vec<usize>>>);
+
+impl Paths {
+    fn new(outline: &Outline) -> Self {
+        fn walk(
+            ret: &mut BTreeMap<String, Vec<Vec<usize>>>,
+            path: &str,
+            idx: &mut Vec<usize>,
+            outline: &Outline,
+        ) {
+            for (i, s) in outline.children.iter().enumerate() {
+                let path = if path.is_empty() {
+                    head_name(s).to_owned()
+                } else {
+                    format!("{path}/{}", head_name(s))
+                };
+                idx.push(i);
+                ret.entry(path.clone()).or_default().push(idx.clone());
+                walk(ret, &path, idx, &s.body);
+                idx.pop();
+            }
+        }
+
+        let mut ret = BTreeMap::new();
+        walk(&mut ret, "", &mut Vec::new(), outline);
+        Paths(ret)
+    }
+
+    fn is_ambiguous(&self, path: &str) -> bool {
+        self.0.get(path).is_some_and(|a| a.len() > 1)
+    }
+
+    fn get(&self, path: &str) -> Option<&[usize]> {
+        match self.0.get(path) {
+            Some(a) if a.len() == 1 => Some(&a[0]),
+            _ => None,
+        }
+    }
+}
+
+fn section<'a>(outline: &'a Outline, idx: &[usize]) -> &'a Section {
+    let s = &outline.children[idx[0]];
+    if idx.len() == 1 {
+        s
+    } else {
+        section(&s.body, &idx[1..])
+    }
+}
+
+fn section_mut<'a>(outline: &'a mut Outline, idx: &[usize]) -> &'a mut Section {
+    let s = &mut outline.children[idx[0]];
+    if idx.len() == 1 {
+        s
+    } else {
+        section_mut(&mut s.body, &idx[1..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_views() {
+        let mut outline: Outline = idm::from_str(
+            "\
+Rust
+  :tags rust
+  Tokio
+    :tags async
+  Serde
+    :tags serde
+Cooking
+  Pasta
+    :tags food todo
+Fix parser #todo",
+        )
+        .unwrap();
+        let views =
+            Views::parse("-- Views\nrust-async rust async\ntodo todo").unwrap();
+
+        let written = idm::to_string(
+            &materialize(&outline, &views, &TagHierarchy::default()).unwrap(),
+        )
+        .unwrap();
+        let heads: Vec<Vec<String>> = idm::from_str::<Outline>(&written)
+            .unwrap()
+            .children
+            .iter()
+            .map(|v| v.body.children.iter().map(|s| s.head.clone()).collect())
+            .collect();
+        assert_eq!(
+            heads,
+            vec![vec!["Tokio"], vec!["Pasta", "Fix parser #todo"]]
+        );
+
+        // Unedited views change nothing.
+        let views: Outline = idm::from_str(&written).unwrap();
+        assert_eq!(apply(&mut outline.clone(), &views).unwrap(), 0);
+
+        let edited: Outline =
+            idm::from_str(&written.replace(":tags food todo", ":tags food"))
+                .unwrap();
+        assert_eq!(apply(&mut outline, &edited).unwrap(), 1);
+        assert_eq!(outline.children[1].body.children[0].tags(), vec!["food"]);
+        assert!(!outline.children[1].body.children[0]
+            .body
+            .attrs
+            .contains_key(VIEW_SOURCE));
+
+        // The original changed since the view was written.
+        assert!(apply(&mut outline, &edited).is_err());
+    }
+}
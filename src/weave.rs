@@ -9,14 +9,14 @@ use std::{
 
 use anyhow::{bail, Result};
 use base64::prelude::*;
-use ont::{Outline, Section};
 use lazy_regex::regex;
+use ont::{Outline, Section};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
 use crate::IoPipe;
 
-const OUTPUT_MARKER: &str = "==";
+pub use ont::parse::{weave_filename, OUTPUT_MARKER};
 
 pub fn run(force: bool, io: IoPipe) -> Result<()> {
     // Synthesize a toplevel section.
@@ -197,8 +197,9 @@ impl TryFrom<&Section> for Script {
             self_hash != metadata.input[0]
         };
 
-        let file_path = PathBuf::from(if outline_path == "-" {
-            self_hash.clone()
+        let file_path = PathBuf::from(if is_anonymous(outline_path) {
+            // Keep the extension of anonymous scripts.
+            format!("{self_hash}{}", &outline_path[1..])
         } else {
             outline_path.to_owned()
         });
@@ -214,22 +215,39 @@ impl TryFrom<&Section> for Script {
     }
 }
 
-fn weave_filename(head: &str) -> Option<&str> {
-    let head = head.trim().strip_prefix('>')?;
-
-    // XXX Filename validation could be more robust. Must be a
-    // valid filename that isn't trying to escape containment with
-    // ".." or by starting with '/', but it can include
-    // subdirectory structure.
-    if head.chars().any(|c| c.is_whitespace())
-        || head.contains("..")
-        || head.starts_with('/')
-    {
-        return None;
+/// Headline of a weave script without a file name, with the language as the
+/// extension, `>-.python`.
+pub fn anonymous_script(lang: &str) -> String {
+    if regex!(r"^[A-Za-z0-9_-]+$").is_match(lang) {
+        format!(">-.{lang}")
+    } else {
+        ">-".into()
     }
-    if !regex!(r"^[A-Za-z0-9_-][.A-Za-z0-9_/-]*$").is_match(head.as_ref()) {
-        return None;
+}
+
+/// Whether a weave script name is just `-` or a language extension like
+/// `-.python` instead of a file name.
+pub fn is_anonymous(name: &str) -> bool {
+    name == "-" || name.starts_with("-.")
+}
+
+/// Programming language of a weave script, for code block markup.
+pub fn script_language(name: &str, body: &Outline) -> String {
+    // Use the file extension if there is one.
+    if let Some((_, ext)) = name.rsplit_once('.') {
+        return ext.to_owned();
     }
 
-    Some(head)
+    // Otherwise take the interpreter from the shebang line.
+    let first = body.children.first().map(|s| s.head.as_str()).unwrap_or("");
+    let Some(shebang) = first.strip_prefix("#!") else {
+        return String::new();
+    };
+    let mut words = shebang.split_whitespace();
+    let mut cmd = words.next().unwrap_or("");
+    cmd = cmd.rsplit('/').next().unwrap_or(cmd);
+    if cmd == "env" {
+        cmd = words.find(|w| !w.starts_with('-')).unwrap_or("");
+    }
+    cmd.to_owned()
 }
@@ -0,0 +1,397 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use ont::{parse, Outline, Section};
+
+use crate::IoPipe;
+
+/// Headline of the generated backlinks section.
+const BACKLINKS_SECTION: &str = "Backlinks";
+
+/// Attribute that marks the backlinks section as generated, so sections the
+/// user named "Backlinks" are left alone.
+const GENERATED_ATTR: &str = "generated";
+
+/// Whether the section is a backlinks section written by `backlinks`.
+pub fn is_generated_backlinks(s: &Section) -> bool {
+    s.head == BACKLINKS_SECTION
+        && s.body.attrs.get(GENERATED_ATTR).map(String::as_str)
+            == Some("backlinks")
+}
+
+/// Ways to write backlinks into wiki pages.
+#[derive(Copy, Clone, Debug, ValueEnum)]
+pub enum WriteBacklinks {
+    /// Set a `:backlinks` attribute.
+    Attr,
+    /// Add a "Backlinks" section at the end of the page.
+    Section,
+}
+
+pub fn backlinks(io: IoPipe, write: Option<WriteBacklinks>) -> Result<()> {
+    let mut outline = io.read_outline()?;
+    let backlinks = backlink_index(&outline);
+
+    let Some(write) = write else {
+        // Just list the backlinks of the pages that exist.
+        let pages = pages(&outline);
+        let list: Outline = backlinks
+            .into_iter()
+            .filter(|(title, _)| pages.contains_key(title))
+            .map(|(title, links)| Section::new(title, lines(links)))
+            .collect();
+        return io.write_text(idm::to_string(&list)?);
+    };
+
+    for (generated, s) in outline.context_iter_mut(false) {
+        // Don't descend into backlinks we just wrote.
+        if *generated || is_generated_backlinks(s) {
+            *generated = true;
+            continue;
+        }
+
+        let Some(title) = s.wiki_title() else {
+            continue;
+        };
+        let links = backlinks.get(title).cloned().unwrap_or_default();
+
+        // Rewrite or remove existing backlinks.
+        s.body.attrs.shift_remove("backlinks");
+        s.body.children.retain(|c| !is_generated_backlinks(c));
+        if links.is_empty() {
+            continue;
+        }
+
+        match write {
+            WriteBacklinks::Attr => {
+                s.body.set("backlinks", &links)?;
+            }
+            WriteBacklinks::Section => {
+                // List items so the WikiWords don't read as page headlines.
+                let items =
+                    links.into_iter().map(|a| format!("- {a}")).collect();
+                let mut body = lines(items);
+                body.attrs.insert(GENERATED_ATTR.into(), "backlinks".into());
+                s.body.push(Section::new(BACKLINKS_SECTION, body));
+            }
+        }
+    }
+
+    io.write(&outline)
+}
+
+/// Report WikiWords that have no page and wiki pages nothing links to.
+pub fn lint(io: IoPipe) -> Result<()> {
+    let outline = io.read_outline()?;
+    let pages = pages(&outline);
+    let mentions = mentions(&outline);
+
+    let mut broken: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for m in &mentions {
+        if !pages.contains_key(&m.target) {
+            let locations = broken.entry(&m.target).or_default();
+            if !locations.contains(&m.path) {
+                locations.push(m.path.clone());
+            }
+        }
+    }
+
+    let mut orphans: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (title, paths) in &pages {
+        if !mentions.iter().any(|m| &m.target == title) {
+            orphans.insert(title, paths.clone());
+        }
+    }
+
+    let mut report = Outline::default();
+    for (head, items) in [("broken", broken), ("orphans", orphans)] {
+        if !items.is_empty() {
+            report.push(Section::new(
+                head,
+                items
+                    .into_iter()
+                    .map(|(title, paths)| Section::new(title, lines(paths)))
+                    .collect(),
+            ));
+        }
+    }
+    io.write_text(idm::to_string(&report)?)
+}
+
+/// Map wiki page titles to the headline paths of the sections defining them.
+pub fn pages(outline: &Outline) -> BTreeMap<String, Vec<String>> {
+    let mut ret: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (path, s) in outline.context_iter(Vec::new()) {
+        path.push(section_name(s));
+        if let Some(title) = s.wiki_title() {
+            ret.entry(title.to_owned())
+                .or_default()
+                .push(path.join("/"));
+        }
+    }
+    ret
+}
+
+/// Name of a section in a headline path.
+fn section_name(s: &Section) -> String {
+    s.head
+        .strip_suffix('/')
+        .unwrap_or(&s.head)
+        .trim()
+        .to_owned()
+}
+
+fn lines(lines: Vec<String>) -> Outline {
+    lines
+        .into_iter()
+        .map(|a| Section::new(a, Outline::default()))
+        .collect()
+}
+
+/// Rename a wiki page and all the mentions of it in headlines, attribute
+/// values and body text outside weave scripts. Return the number of places
+/// changed.
+pub fn rename_page(outline: &mut Outline, old: &str, new: &str) -> usize {
+    let mut count = 0;
+    for s in outline.content_iter_mut() {
+        // Weave script names and code are literal text.
+        if s.is_verbatim() {
+            continue;
+        }
+        if let Some(head) = rename_word(&s.head, old, new) {
+            s.head = head;
+            count += 1;
+        }
+        for value in s.body.attrs.values_mut() {
+            if let Some(v) = rename_word(value, old, new) {
+                *value = v;
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Replace the WikiWord in text, skipping URLs like `parse::wiki_words`.
+fn rename_word(text: &str, old: &str, new: &str) -> Option<String> {
+    if !parse::wiki_words(text).any(|w| w == old) {
+        return None;
+    }
+
+    let mut ret = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find(|c: char| !c.is_whitespace()) {
+        ret.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let token = &rest[..end];
+        let word = token.trim_matches(|c: char| !c.is_alphanumeric());
+        if word == old && !token.contains("://") {
+            ret.push_str(&token.replacen(old, new, 1));
+        } else {
+            ret.push_str(token);
+        }
+        rest = &rest[end..];
+    }
+    ret.push_str(rest);
+    Some(ret)
+}
+
+/// Mention of a WikiWord in an outline.
+#[derive(Clone, Debug)]
+pub struct Mention {
+    /// Slash-separated headline path of the section with the mention, in
+    /// the same form as `--at` paths.
+    pub path: String,
+    /// Wiki page the mention is on, if any.
+    pub page: Option<String>,
+    /// The mentioned WikiWord.
+    pub target: String,
+}
+
+impl Mention {
+    /// Name of the mentioning section to show to the user, the wiki page if
+    /// there is one.
+    pub fn source(&self) -> &str {
+        self.page.as_deref().unwrap_or(&self.path)
+    }
+}
+
+#[derive(Clone, Default)]
+struct Context {
+    path: Vec<String>,
+    page: Option<String>,
+    /// Inside a generated backlinks section.
+    generated: bool,
+}
+
+/// Find WikiWord mentions in headlines, attribute values and body text.
+///
+/// Text lines without a body of their own count as mentions from the
+/// section they're in. Wiki pages mentioning themselves and generated
+/// backlinks are not counted.
+pub fn mentions(outline: &Outline) -> Vec<Mention> {
+    let mut ret = Vec::new();
+
+    for (ctx, s) in outline.context_iter(Context::default()) {
+        let parent = ctx.clone();
+
+        ctx.path.push(section_name(s));
+        if let Some(title) = s.wiki_title() {
+            ctx.page = Some(title.to_owned());
+        }
+        if parent.page.is_some() && is_generated_backlinks(s) {
+            ctx.generated = true;
+        }
+        if ctx.generated {
+            continue;
+        }
+
+        // Text lines are the body of their parent section.
+        let at = if s.body.is_empty() && !parent.path.is_empty() {
+            &parent
+        } else {
+            &*ctx
+        };
+
+        for word in mentioned_words(s) {
+            if at.page.as_deref() == Some(word) {
+                continue;
+            }
+            ret.push(Mention {
+                path: at.path.join("/"),
+                page: at.page.clone(),
+                target: word.to_owned(),
+            });
+        }
+    }
+
+    ret
+}
+
+/// WikiWords a section mentions in its headline and attribute values.
+///
+/// The headline of a wiki page is its title, not a mention, and the
+/// `:backlinks` attribute is generated.
+pub fn mentioned_words(s: &Section) -> Vec<&str> {
+    let mut ret = Vec::new();
+    if s.wiki_title().is_none() {
+        ret.extend(parse::wiki_words(&s.head));
+    }
+    for (k, v) in &s.body.attrs {
+        if k != "backlinks" {
+            ret.extend(parse::wiki_words(v));
+        }
+    }
+    ret
+}
+
+/// Map wiki titles to the deduplicated list of sections that mention them.
+pub fn backlink_index(outline: &Outline) -> BTreeMap<String, Vec<String>> {
+    let mut ret: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for m in mentions(outline) {
+        let links = ret.entry(m.target.clone()).or_default();
+        if !links.iter().any(|a| a == m.source()) {
+            links.push(m.source().to_owned());
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backlinks() {
+        let outline: Outline = idm::from_str(
+            "\
+notes
+  OntTool
+    :see-also IdmFormat
+    Parses IdmFormat, see OntTool.
+  Random thoughts
+    About IdmFormat
+  IdmFormat
+    Backlinks
+      :generated backlinks
+      - Stale OntTool
+  RustLang
+    Backlinks
+      My own notes on OntTool",
+        )
+        .unwrap();
+
+        let index = backlink_index(&outline);
+        assert_eq!(
+            index.get("IdmFormat").unwrap(),
+            &vec!["OntTool".to_owned(), "notes/Random thoughts".to_owned()]
+        );
+        // Self-mentions and generated sections don't count, but sections
+        // the user named Backlinks do.
+        assert_eq!(index.get("OntTool").unwrap(), &vec!["RustLang".to_owned()]);
+    }
+
+    #[test]
+    fn test_rename_page() {
+        let mut outline: Outline = idm::from_str(
+            "\
+OntTool *
+  :see-also OntTool IdmFormat
+  Part of OntToolkit, see (OntTool).
+  https://example.com/OntTool
+  >-
+    print(\"OntTool\")
+  ==
+    OntTool",
+        )
+        .unwrap();
+
+        assert_eq!(rename_page(&mut outline, "OntTool", "IdmTool"), 3);
+        assert_eq!(
+            idm::to_string(&outline).unwrap(),
+            "\
+IdmTool *
+  :see-also IdmTool IdmFormat
+  Part of OntToolkit, see (IdmTool).
+  https://example.com/OntTool
+  >-
+    print(\"OntTool\")
+  ==
+    OntTool
+"
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let outline: Outline = idm::from_str(
+            "\
+notes
+  OntTool
+    Uses IdmFormat and NoSuchPage.
+  Orphan
+    OrphanPage
+      Nobody links here, see OntTool",
+        )
+        .unwrap();
+
+        let pages = pages(&outline);
+        assert_eq!(pages["OrphanPage"], vec!["notes/Orphan/OrphanPage"]);
+
+        let mentions = mentions(&outline);
+        let broken: Vec<_> = mentions
+            .iter()
+            .filter(|m| !pages.contains_key(&m.target))
+            .map(|m| (m.target.as_str(), m.path.as_str()))
+            .collect();
+        assert_eq!(
+            broken,
+            vec![
+                ("IdmFormat", "notes/OntTool"),
+                ("NoSuchPage", "notes/OntTool")
+            ]
+        );
+    }
+}